@@ -0,0 +1,306 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use log::{info, warn};
+use noodles_bam as bam;
+use noodles_fastq as fastq;
+use noodles_sam::{
+    self as sam,
+    alignment::record::data::field::{value::Array, Tag, Value},
+};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::compression;
+use crate::extract::original_read;
+use crate::sort::parse_tag;
+
+#[derive(Args, Debug)]
+pub struct Bam2fqArgs {
+    /// BAM or SAM input file; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file for all reads when not split into pairs,
+    /// compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath, conflicts_with = "read1")]
+    output: Option<PathBuf>,
+
+    /// Output file of the first reads of pairs
+    #[arg(short = '1', long = "read1", requires = "read2", value_hint = ValueHint::FilePath)]
+    read1: Option<PathBuf>,
+
+    /// Output file of the second reads of pairs
+    #[arg(short = '2', long = "read2", requires = "read1", value_hint = ValueHint::FilePath)]
+    read2: Option<PathBuf>,
+
+    /// Output file of unpaired reads and reads whose mate is missing.
+    /// They are dropped if not set
+    #[arg(short = 's', long = "singletons", requires = "read1", value_hint = ValueHint::FilePath)]
+    singletons: Option<PathBuf>,
+
+    /// Data fields to copy into the FASTQ comment, e.g. MM,ML or CB,UB
+    #[arg(short = 'T', long = "tags", value_delimiter = ',', value_parser = parse_tag)]
+    tags: Vec<Tag>,
+}
+
+fn write_array_values<W, N, I>(writer: &mut W, values: I) -> io::Result<()>
+where
+    W: Write,
+    N: std::fmt::Display,
+    I: Iterator<Item = io::Result<N>>,
+{
+    for value in values {
+        write!(writer, ",{}", value?)?;
+    }
+    Ok(())
+}
+
+/// Writes a data field as SAM text, e.g. `CB:Z:ACGT` or `ML:B:C,12,200`.
+fn write_field<W: Write>(writer: &mut W, tag: Tag, value: &Value<'_>) -> io::Result<()> {
+    writer.write_all(tag.as_ref())?;
+
+    match value {
+        Value::Character(c) => write!(writer, ":A:{}", char::from(*c)),
+        Value::Float(n) => write!(writer, ":f:{n}"),
+        Value::String(s) => {
+            writer.write_all(b":Z:")?;
+            writer.write_all(s)
+        }
+        Value::Hex(s) => {
+            writer.write_all(b":H:")?;
+            writer.write_all(s)
+        }
+        Value::Array(array) => match array {
+            Array::Int8(values) => {
+                writer.write_all(b":B:c")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::UInt8(values) => {
+                writer.write_all(b":B:C")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::Int16(values) => {
+                writer.write_all(b":B:s")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::UInt16(values) => {
+                writer.write_all(b":B:S")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::Int32(values) => {
+                writer.write_all(b":B:i")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::UInt32(values) => {
+                writer.write_all(b":B:I")?;
+                write_array_values(writer, values.iter())
+            }
+            Array::Float(values) => {
+                writer.write_all(b":B:f")?;
+                write_array_values(writer, values.iter())
+            }
+        },
+        // every other value is an integer
+        _ => write!(writer, ":i:{}", value.as_int().unwrap_or_default()),
+    }
+}
+
+/// The read in its original orientation, with the chosen data fields as its comment.
+fn fastq_record(record: &dyn sam::alignment::Record, tags: &[Tag]) -> io::Result<fastq::Record> {
+    let name = record
+        .name()
+        .map(|name| name.as_bytes().to_vec())
+        .unwrap_or_else(|| b"*".to_vec());
+    let (sequence, quality_scores) = original_read(record, true)?;
+
+    let data = record.data();
+    let mut comment = Vec::new();
+    for &tag in tags {
+        if let Some(value) = data.get(&tag).transpose()? {
+            if !comment.is_empty() {
+                comment.push(b'\t');
+            }
+            write_field(&mut comment, tag, &value)?;
+        }
+    }
+
+    Ok(fastq::Record::new(
+        fastq::record::Definition::new(name, comment),
+        sequence,
+        quality_scores,
+    ))
+}
+
+/// Writes pairs to the R1 and R2 files once both mates have been read.
+struct PairWriter {
+    read1: compression::Writer,
+    read2: compression::Writer,
+    singletons: Option<compression::Writer>,
+    pending: HashMap<Vec<u8>, (bool, fastq::Record)>,
+    pairs: u64,
+    singleton_count: u64,
+}
+
+impl PairWriter {
+    fn new(args: &Bam2fqArgs) -> io::Result<Self> {
+        Ok(Self {
+            read1: compression::create(args.read1.as_ref())?,
+            read2: compression::create(args.read2.as_ref())?,
+            singletons: args
+                .singletons
+                .as_ref()
+                .map(|dst| compression::create(Some(dst)))
+                .transpose()?,
+            pending: HashMap::new(),
+            pairs: 0,
+            singleton_count: 0,
+        })
+    }
+
+    fn write_singleton(&mut self, record: &fastq::Record) -> io::Result<()> {
+        self.singleton_count += 1;
+        match self.singletons.as_mut() {
+            Some(writer) => fastq::Writer::new(writer).write_record(record),
+            None => Ok(()),
+        }
+    }
+
+    fn write(
+        &mut self,
+        flags: sam::alignment::record::Flags,
+        record: fastq::Record,
+    ) -> io::Result<()> {
+        let is_first = flags.is_first_segment();
+        if !flags.is_segmented() || is_first == flags.is_last_segment() {
+            return self.write_singleton(&record);
+        }
+
+        match self.pending.remove(record.name()) {
+            Some((mate_is_first, mate)) if mate_is_first != is_first => {
+                let (r1, r2) = if is_first {
+                    (&record, &mate)
+                } else {
+                    (&mate, &record)
+                };
+                fastq::Writer::new(&mut self.read1).write_record(r1)?;
+                fastq::Writer::new(&mut self.read2).write_record(r2)?;
+                self.pairs += 1;
+                Ok(())
+            }
+            Some((_, mate)) => {
+                self.write_singleton(&mate)?;
+                self.pending
+                    .insert(record.name().to_vec(), (is_first, record));
+                Ok(())
+            }
+            None => {
+                self.pending
+                    .insert(record.name().to_vec(), (is_first, record));
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let mut pending: Vec<_> = std::mem::take(&mut self.pending).into_values().collect();
+        pending.sort_by(|(_, a), (_, b)| a.name().cmp(b.name()));
+        for (_, record) in &pending {
+            self.write_singleton(record)?;
+        }
+
+        info!("wrote {} pairs", self.pairs);
+        if self.singletons.is_none() && self.singleton_count > 0 {
+            warn!(
+                "dropped {} unpaired reads, write them with --singletons",
+                self.singleton_count
+            );
+        } else {
+            info!("wrote {} singletons", self.singleton_count);
+        }
+
+        self.read1.finish()?;
+        self.read2.finish()?;
+        if let Some(singletons) = self.singletons {
+            singletons.finish()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn bam2fq(args: &Bam2fqArgs) -> Result<()> {
+    let mut inner = compression::open(&args.input)
+        .with_context(|| format!("failed to open {}", args.input.display()))?;
+
+    let mut reader: Box<dyn sam::alignment::io::Read<Box<dyn BufRead>>> =
+        if inner.fill_buf()?.starts_with(b"BAM\x01") {
+            Box::new(bam::io::Reader::from(inner))
+        } else {
+            Box::new(sam::io::Reader::new(inner))
+        };
+    let header = reader.read_alignment_header()?;
+
+    let mut pair_writer = match &args.read1 {
+        Some(_) => Some(PairWriter::new(args)?),
+        None => None,
+    };
+    let mut output = match &pair_writer {
+        Some(_) => None,
+        None => Some(compression::create(args.output.as_ref())?),
+    };
+
+    for result in reader.alignment_records(&header) {
+        let record = result?;
+        let flags = record.flags()?;
+        if flags.is_secondary() || flags.is_supplementary() {
+            continue;
+        }
+
+        let fastq_record = fastq_record(&*record, &args.tags)?;
+        match (&mut pair_writer, &mut output) {
+            (Some(pair_writer), _) => pair_writer.write(flags, fastq_record)?,
+            (None, Some(output)) => fastq::Writer::new(output).write_record(&fastq_record)?,
+            (None, None) => unreachable!(),
+        }
+    }
+
+    if let Some(pair_writer) = pair_writer {
+        pair_writer.finish()?;
+    }
+    if let Some(output) = output {
+        output.finish()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sam::alignment::record_buf::data::field::{value::Array as ArrayBuf, Value as ValueBuf};
+
+    fn format_field(tag: Tag, value: ValueBuf) -> String {
+        let mut buf = Vec::new();
+        write_field(&mut buf, tag, &Value::from(&value)).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_write_field() {
+        assert_eq!(
+            format_field(Tag::new(b'C', b'B'), ValueBuf::from("ACGT")),
+            "CB:Z:ACGT"
+        );
+        assert_eq!(
+            format_field(Tag::new(b'H', b'P'), ValueBuf::UInt8(1)),
+            "HP:i:1"
+        );
+        assert_eq!(
+            format_field(
+                Tag::new(b'M', b'L'),
+                ValueBuf::Array(ArrayBuf::UInt8(vec![12, 200]))
+            ),
+            "ML:B:C,12,200"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use noodles_bgzf as bgzf;
+
+/// The longest magic number we look at: a BGZF header.
+const MAGIC_LEN: usize = 18;
+
+/// How a stream is compressed, from its magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bgzf,
+    Zstd,
+}
+
+impl Compression {
+    pub fn detect(magic: &[u8]) -> Self {
+        match magic {
+            [0x1f, 0x8b, 0x08, flags, ..]
+                if flags & 0x04 != 0 && magic.get(12..14) == Some(b"BC") =>
+            {
+                Self::Bgzf
+            }
+            [0x1f, 0x8b, ..] => Self::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(src: P) -> io::Result<Self> {
+        let mut magic = Vec::with_capacity(MAGIC_LEN);
+        File::open(src)?
+            .take(MAGIC_LEN as u64)
+            .read_to_end(&mut magic)?;
+        Ok(Self::detect(&magic))
+    }
+
+    fn from_extension<P: AsRef<Path>>(dst: P) -> Self {
+        match dst.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz" | "bgz") => Self::Bgzf,
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+}
+
+fn is_stdio<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().as_os_str() == "-"
+}
+
+/// Opens a file, or stdin for `-`, decompressing gzip, BGZF and zstd input.
+pub fn open<P: AsRef<Path>>(src: P) -> io::Result<Box<dyn BufRead>> {
+    let mut inner: Box<dyn Read> = if is_stdio(&src) {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(src)?)
+    };
+
+    // stdin cannot seek, so the magic bytes are read and put back in front
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    (&mut inner)
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let inner = Cursor::new(magic).chain(inner);
+
+    let reader: Box<dyn BufRead> = match compression {
+        Compression::None => Box::new(BufReader::new(inner)),
+        Compression::Bgzf => Box::new(bgzf::Reader::new(inner)),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(inner))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(inner)?)),
+    };
+
+    Ok(reader)
+}
+
+/// An output stream, compressed according to the file extension.
+pub enum Writer {
+    Plain(BufWriter<Box<dyn Write>>),
+    Bgzf(bgzf::Writer<Box<dyn Write>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+}
+
+impl Writer {
+    /// Writes any buffered data and the end of the compressed stream.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(mut writer) => writer.flush(),
+            Self::Bgzf(writer) => writer.finish()?.flush(),
+            Self::Zstd(writer) => writer.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Bgzf(writer) => writer.write(buf),
+            Self::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Bgzf(writer) => writer.flush(),
+            Self::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Creates a file, or stdout for `None` or `-`. `.gz`/`.bgz` files are
+/// compressed with BGZF and `.zst` files with zstd.
+pub fn create<P: AsRef<Path>>(dst: Option<P>) -> io::Result<Writer> {
+    match dst {
+        Some(dst) if !is_stdio(&dst) => wrap(
+            Box::new(File::create(&dst)?),
+            Compression::from_extension(&dst),
+        ),
+        _ => wrap(Box::new(io::stdout()), Compression::None),
+    }
+}
+
+/// Opens a file for appending, compressed like [`create`]. Compressed data is
+/// appended as a new gzip member or zstd frame, which decoders read through.
+pub fn append<P: AsRef<Path>>(dst: P) -> io::Result<Writer> {
+    let file = OpenOptions::new().create(true).append(true).open(&dst)?;
+    wrap(Box::new(file), Compression::from_extension(&dst))
+}
+
+/// Every variant buffers its output, so `inner` is written to unbuffered.
+fn wrap(inner: Box<dyn Write>, compression: Compression) -> io::Result<Writer> {
+    let writer = match compression {
+        Compression::Bgzf | Compression::Gzip => Writer::Bgzf(bgzf::Writer::new(inner)),
+        Compression::Zstd => Writer::Zstd(zstd::Encoder::new(inner, 0)?),
+        Compression::None => Writer::Plain(BufWriter::new(inner)),
+    };
+
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let bgzf = [
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C',
+            0x02, 0x00, 0x1b, 0x00,
+        ];
+        assert_eq!(Compression::detect(&bgzf), Compression::Bgzf);
+        assert_eq!(
+            Compression::detect(&[0x1f, 0x8b, 0x08, 0x00]),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(b">sq0\n"), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+}
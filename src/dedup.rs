@@ -0,0 +1,191 @@
+use ahash::AHashMap;
+use anyhow::{bail, Context, Result};
+use bio::alphabets::dna;
+use bstr::ByteSlice;
+use clap::{Args, ValueHint};
+use log::info;
+use noodles_fastq as fastq;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::compression;
+use crate::extract::SequenceFormat;
+use crate::filter;
+
+#[derive(Args, Debug)]
+pub struct DedupArgs {
+    /// FASTQ or FASTA input file, plain or compressed; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Remove reads with the same sequence instead of the same name
+    #[arg(short = 's', long = "by-seq")]
+    by_seq: bool,
+
+    /// Compare sequences ignoring case
+    #[arg(short = 'i', long = "ignore-case", requires = "by_seq")]
+    ignore_case: bool,
+
+    /// Also treat a sequence and its reverse complement as duplicates
+    #[arg(short = 'r', long = "revcomp", requires = "by_seq")]
+    revcomp: bool,
+
+    /// Write the names of the removed reads next to the name of the read they were merged into
+    #[arg(short = 'D', long = "dup-table", value_hint = ValueHint::FilePath)]
+    dup_table: Option<PathBuf>,
+}
+
+/// The reverse complement of a DNA or RNA read, RNA reads keep U instead of T.
+fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    if !sequence.iter().any(|base| matches!(base, b'U' | b'u')) {
+        return dna::revcomp(sequence);
+    }
+
+    // bio only complements DNA, so the read goes through its DNA form
+    let dna: Vec<u8> = sequence
+        .iter()
+        .map(|&base| match base {
+            b'U' => b'T',
+            b'u' => b't',
+            base => base,
+        })
+        .collect();
+    dna::revcomp(dna)
+        .into_iter()
+        .map(|base| match base {
+            b'T' => b'U',
+            b't' => b'u',
+            base => base,
+        })
+        .collect()
+}
+
+impl DedupArgs {
+    /// The value reads are compared by, before it is hashed.
+    fn normalized(&self, record: &fastq::Record) -> Vec<u8> {
+        if !self.by_seq {
+            return record.name().to_vec();
+        }
+
+        let mut sequence = record.sequence().to_vec();
+        if self.ignore_case {
+            sequence.make_ascii_uppercase();
+        }
+        if self.revcomp {
+            // the smaller of both strands is the same for a read and its reverse complement
+            let reverse = reverse_complement(&sequence);
+            if reverse < sequence {
+                return reverse;
+            }
+        }
+        sequence
+    }
+
+    /// The hash of the value reads are compared by, so only 16 bytes are kept per read.
+    fn key(&self, record: &fastq::Record) -> u128 {
+        xxh3_128(&self.normalized(record))
+    }
+}
+
+pub fn dedup(args: &DedupArgs) -> Result<()> {
+    let mut reader = compression::open(&args.input)
+        .with_context(|| format!("failed to open {}", args.input.display()))?;
+    let format = match reader.fill_buf()?.first() {
+        Some(b'>') => SequenceFormat::Fasta,
+        Some(b'@') | None => SequenceFormat::Fastq,
+        Some(_) => bail!("{} is not a FASTQ or FASTA file", args.input.display()),
+    };
+
+    let mut output = compression::create(args.output.as_ref())?;
+    let mut writer = filter::Writer::new(&mut output, format);
+
+    // key hashes of the kept reads, with their index in `merged`
+    let mut seen: AHashMap<u128, usize> = AHashMap::new();
+    // names of the kept reads and of their duplicates, only collected for --dup-table
+    let mut merged: Vec<(Vec<u8>, Vec<Vec<u8>>)> = Vec::new();
+    let mut total = 0u64;
+
+    filter::for_each_record(reader, format, |record| {
+        total += 1;
+        let key = args.key(&record);
+
+        match seen.get(&key) {
+            Some(&i) => {
+                if args.dup_table.is_some() {
+                    merged[i].1.push(record.name().to_vec());
+                }
+                Ok(())
+            }
+            None => {
+                if args.dup_table.is_some() {
+                    merged.push((record.name().to_vec(), Vec::new()));
+                }
+                seen.insert(key, merged.len().saturating_sub(1));
+                writer.write_record(&record)
+            }
+        }
+    })?;
+
+    output.finish()?;
+    info!("kept {} of {total} reads", seen.len());
+
+    if let Some(dst) = &args.dup_table {
+        let mut table = compression::create(Some(dst))
+            .with_context(|| format!("failed to create {}", dst.display()))?;
+        writeln!(table, "name\tduplicates\tduplicate_names")?;
+        for (name, duplicates) in merged.iter().filter(|(_, dups)| !dups.is_empty()) {
+            writeln!(
+                table,
+                "{}\t{}\t{}",
+                name.as_bstr(),
+                duplicates.len(),
+                duplicates.join(&b","[..]).as_bstr()
+            )?;
+        }
+        table.finish()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: DedupArgs,
+    }
+
+    fn normalized(args: &[&str], sequence: &str) -> (Vec<u8>, u128) {
+        let cli = Cli::parse_from(["dedup", "-"].iter().chain(args));
+        let record = fastq::Record::new(fastq::record::Definition::new("r1", ""), sequence, "");
+        (cli.args.normalized(&record), cli.args.key(&record))
+    }
+
+    #[test]
+    fn test_key() {
+        let cases: [(&[&str], _, &[u8]); 7] = [
+            (&[], "ACGT", b"r1"),
+            (&["-s"], "aCGG", b"aCGG"),
+            (&["-s", "-i"], "aCGG", b"ACGG"),
+            (&["-s", "-r"], "GGCA", b"GGCA"),
+            (&["-s", "-r"], "TGCC", b"GGCA"),
+            // RNA reads match their reverse complement as RNA
+            (&["-s", "-r"], "AAGU", b"AAGU"),
+            (&["-s", "-r"], "ACUU", b"AAGU"),
+        ];
+        for (args, sequence, expected) in cases {
+            let (normalized, key) = normalized(args, sequence);
+            assert_eq!(normalized, expected);
+            assert_eq!(key, xxh3_128(expected));
+        }
+    }
+}
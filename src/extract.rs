@@ -1,33 +1,449 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum, ValueHint};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fmt,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
     path::Path,
+    str::FromStr,
 };
 
+use log::{info, warn};
 use noodles_bam as bam;
+use noodles_bam::bai;
+use noodles_bgzf as bgzf;
+use noodles_core::{Position, Region};
+use noodles_cram as cram;
+use noodles_csi::{self as csi, BinningIndex};
+use noodles_fasta as fasta;
+use noodles_fastq as fastq;
 use noodles_sam::{
     self as sam,
+    alignment::io::Write as _,
+    alignment::record::{
+        cigar::op::Kind,
+        data::field::{Tag, Value},
+    },
     header::record::value::map::program,
     header::record::value::{map::Program, Map},
 };
 
+use bio::alphabets::dna;
 use bstr::BString;
 use clap::crate_version;
+use regex::bytes::RegexSet;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
-fn writer(file: Option<&PathBuf>, is_bam: bool) -> Result<Box<dyn sam::alignment::io::Write>> {
-    let sink: Box<dyn io::Write> = if let Some(file) = file {
-        Box::new(File::create(file)?)
-    } else {
-        Box::new(io::stdout().lock())
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Sam,
+    Bam,
+    Cram,
+}
+
+impl OutputFormat {
+    fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "sam" => Some(Self::Sam),
+            "bam" => Some(Self::Bam),
+            "cram" => Some(Self::Cram),
+            _ => None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceFormat {
+    Fastq,
+    Fasta,
+}
+
+impl SequenceFormat {
+    fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "fq" | "fastq" => Some(Self::Fastq),
+            "fa" | "fasta" => Some(Self::Fasta),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExtractArgs {
+    /// Read IDs, a comma-separated list or a file with one ID per line.
+    /// An empty string disables name filtering
+    #[arg(value_hint = ValueHint::FilePath)]
+    readids: String,
+
+    /// Bam input file
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Is the output file a BAM file
+    #[arg(short = 'b', default_value = "false")]
+    isbam: bool,
+
+    /// Extract reads whose names do not match the read IDs
+    #[arg(long = "invert", default_value = "false")]
+    invert: bool,
+
+    /// Treat the read IDs as regexes, a file with one pattern per line or a single pattern
+    #[arg(long = "regex", default_value = "false", conflicts_with = "prefix")]
+    regex: bool,
+
+    /// Treat the read IDs as name prefixes (e.g. m64135_201204_204719/)
+    #[arg(long = "prefix", default_value = "false")]
+    prefix: bool,
+
+    /// Only extract reads with all of these flags set (decimal or 0x hex)
+    #[arg(short = 'f', long = "require-flags", value_parser = parse_flags, default_value = "0")]
+    require_flags: u16,
+
+    /// Do not extract reads with any of these flags set (decimal or 0x hex)
+    /// [default: 0, or 0x900 (secondary and supplementary) with --to]
+    #[arg(short = 'F', long = "exclude-flags", value_parser = parse_flags)]
+    exclude_flags: Option<u16>,
+
+    /// Minimum mapping quality
+    #[arg(long = "min-mapq", default_value = "0")]
+    min_mapq: u8,
+
+    /// Only extract primary alignments
+    #[arg(long = "primary-only", conflicts_with = "supplementary_only")]
+    primary_only: bool,
+
+    /// Only extract supplementary alignments
+    #[arg(long = "supplementary-only")]
+    supplementary_only: bool,
+
+    /// Only extract reads with this data field, optionally with a value (e.g. SA or HP:1), can be repeated
+    #[arg(long = "tag")]
+    tags: Vec<TagFilter>,
+
+    /// Only extract reads overlapping the region (e.g. chr1:1000-2000), can be repeated.
+    /// Requires an index (.bai or .csi) next to the BAM
+    #[arg(short = 'r', long = "region")]
+    regions: Vec<Region>,
+
+    /// BED file of regions to extract reads from
+    #[arg(short = 'L', long = "bed", value_hint = ValueHint::FilePath)]
+    bed: Option<PathBuf>,
+
+    /// Output file, default is stdout
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Output format, inferred from the output file extension if not set
+    #[arg(long = "format", value_enum, conflicts_with = "isbam")]
+    format: Option<OutputFormat>,
+
+    /// Reference FASTA (indexed with .fai), required for CRAM output
+    #[arg(short = 'T', long = "reference", value_hint = ValueHint::FilePath)]
+    reference: Option<PathBuf>,
+
+    /// BGZF compression level (0-9) of BAM output
+    #[arg(short = 'l', long = "level", value_parser = clap::value_parser!(u8).range(0..=9))]
+    compression_level: Option<u8>,
+
+    /// Number of threads to decompress the input and compress BAM output
+    #[arg(short = 't', long = "threads", default_value = "1")]
+    threads: NonZeroUsize,
+
+    /// Write reads as FASTQ or FASTA in their original orientation instead of alignments
+    #[arg(long = "to", value_enum, conflicts_with_all = ["format", "isbam"])]
+    to: Option<SequenceFormat>,
+
+    /// Keep soft-clipped bases when writing FASTQ or FASTA
+    #[arg(long = "keep-soft-clips", default_value = "false")]
+    keep_soft_clips: bool,
+
+    /// Write the number of records written and filtered out for each read ID to a TSV file
+    #[arg(
+        long = "summary",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["invert", "regex", "prefix"]
+    )]
+    summary: Option<PathBuf>,
+
+    /// Write the read IDs that were not found to a file
+    #[arg(
+        long = "missing",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["invert", "regex", "prefix"]
+    )]
+    missing: Option<PathBuf>,
+
+    /// Exit with an error if any read ID was not found.
+    /// Like --summary and --missing, this needs exact read IDs
+    #[arg(
+        long = "fail-on-missing",
+        default_value = "false",
+        conflicts_with_all = ["invert", "regex", "prefix"]
+    )]
+    fail_on_missing: bool,
+}
+
+impl ExtractArgs {
+    /// The read format of `--to`, or of a .fq/.fa output.
+    fn sequence_format(&self) -> Option<SequenceFormat> {
+        self.to.or_else(|| {
+            self.format
+                .is_none()
+                .then_some(self.output.as_ref())
+                .flatten()
+                .and_then(SequenceFormat::from_path)
+        })
+    }
+
+    fn output_format(&self) -> Result<OutputFormat> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+
+        if self.isbam {
+            return Ok(OutputFormat::Bam);
+        }
+
+        match &self.output {
+            Some(output) => OutputFormat::from_path(output).with_context(|| {
+                format!(
+                    "cannot infer the output format of {}, use --format",
+                    output.display()
+                )
+            }),
+            None => Ok(OutputFormat::Sam),
+        }
+    }
+}
+
+/// The Phred+33 qualities of a record, `None` when it has none.
+pub fn quality_scores(record: &dyn sam::alignment::Record) -> Option<Vec<u8>> {
+    // Qualities from before recalibration are kept in OQ, already as Phred+33.
+    if let Some(Ok(Value::String(oq))) = record.data().get(&Tag::ORIGINAL_QUALITY_SCORES) {
+        if oq.len() == record.sequence().len() {
+            return Some(oq.to_vec());
+        }
+    }
+
+    let qualities: Vec<u8> = record.quality_scores().iter().collect();
+    // A missing quality string is stored as 0xff.
+    if matches!(qualities.first(), None | Some(0xff)) {
+        return None;
+    }
+    Some(
+        qualities
+            .into_iter()
+            .map(|q| q.saturating_add(b'!').min(b'~'))
+            .collect(),
+    )
+}
+
+/// The read as it came off the sequencer: soft clips optionally removed, reverse-strand
+/// records reverse-complemented and qualities encoded as Phred+33.
+pub fn original_read(
+    record: &dyn sam::alignment::Record,
+    keep_soft_clips: bool,
+) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut sequence: Vec<u8> = record.sequence().iter().collect();
+    // Without qualities, use the same constant quality as fa2fq.
+    let mut qualities = quality_scores(record).unwrap_or_else(|| vec![b'@'; sequence.len()]);
+
+    if !keep_soft_clips {
+        let ops = record
+            .cigar()
+            .iter()
+            .filter(|op| !matches!(op, Ok(op) if op.kind() == Kind::HardClip))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let clip = |op: Option<&sam::alignment::record::cigar::Op>| match op {
+            Some(op) if op.kind() == Kind::SoftClip => op.len(),
+            _ => 0,
+        };
+
+        let start = clip(ops.first());
+        let end = sequence.len().saturating_sub(clip(ops.last()));
+
+        if start < end {
+            sequence = sequence[start..end].to_vec();
+            qualities = qualities[start..end].to_vec();
+        }
+    }
+
+    if record.flags()?.is_reverse_complemented() {
+        sequence = dna::revcomp(&sequence);
+        qualities.reverse();
+    }
+
+    Ok((sequence, qualities))
+}
+
+/// Writes alignment records as FASTQ or FASTA reads.
+pub struct SequenceWriter<W: io::Write> {
+    inner: W,
+    format: SequenceFormat,
+    keep_soft_clips: bool,
+}
+
+impl<W: io::Write> SequenceWriter<W> {
+    pub fn new(inner: W, format: SequenceFormat, keep_soft_clips: bool) -> Self {
+        Self {
+            inner,
+            format,
+            keep_soft_clips,
+        }
+    }
+
+    pub fn write_record(&mut self, record: &dyn sam::alignment::Record) -> io::Result<()> {
+        let name = record
+            .name()
+            .map(|name| name.as_bytes().to_vec())
+            .unwrap_or_else(|| b"*".to_vec());
+        let (sequence, qualities) = original_read(record, self.keep_soft_clips)?;
+
+        match self.format {
+            SequenceFormat::Fastq => {
+                let definition = fastq::record::Definition::new(name, "");
+                fastq::Writer::new(&mut self.inner)
+                    .write_record(&fastq::Record::new(definition, sequence, qualities))
+            }
+            SequenceFormat::Fasta => {
+                let name = String::from_utf8(name)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let definition = fasta::record::Definition::new(name, None);
+                let sequence = fasta::record::Sequence::from(sequence);
+                fasta::Writer::new(&mut self.inner)
+                    .write_record(&fasta::Record::new(definition, sequence))
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+type Sink = Box<dyn io::Write + Send>;
+
+/// The output of extract. Unlike the noodles writers, whose `finish` leaves flushing and the
+/// BGZF end-of-file block to `Drop`, `finish` writes everything out and returns any error.
+pub enum AlignmentWriter {
+    Sam(sam::io::Writer<Sink>),
+    Bam(bam::io::Writer<bgzf::Writer<Sink>>),
+    MultithreadedBam(bam::io::Writer<bgzf::MultithreadedWriter>),
+    Cram(cram::io::Writer<Sink>),
+    Sequence(SequenceWriter<Sink>),
+}
+
+impl AlignmentWriter {
+    pub fn write_header(&mut self, header: &sam::Header) -> io::Result<()> {
+        match self {
+            Self::Sam(writer) => writer.write_alignment_header(header),
+            Self::Bam(writer) => writer.write_alignment_header(header),
+            Self::MultithreadedBam(writer) => writer.write_alignment_header(header),
+            Self::Cram(writer) => writer.write_alignment_header(header),
+            Self::Sequence(_) => Ok(()),
+        }
+    }
+
+    pub fn write_record(
+        &mut self,
+        header: &sam::Header,
+        record: &dyn sam::alignment::Record,
+    ) -> io::Result<()> {
+        match self {
+            Self::Sam(writer) => writer.write_alignment_record(header, record),
+            Self::Bam(writer) => writer.write_alignment_record(header, record),
+            Self::MultithreadedBam(writer) => writer.write_alignment_record(header, record),
+            Self::Cram(writer) => writer.write_alignment_record(header, record),
+            Self::Sequence(writer) => writer.write_record(record),
+        }
+    }
+
+    pub fn finish(self, header: &sam::Header) -> io::Result<()> {
+        match self {
+            Self::Sam(mut writer) => writer.get_mut().flush(),
+            Self::Bam(writer) => writer.into_inner().finish()?.flush(),
+            // these write to an unbuffered file or to stdout, which keeps its own buffer
+            Self::MultithreadedBam(mut writer) => {
+                writer.get_mut().finish()?;
+                io::stdout().flush()
+            }
+            Self::Cram(mut writer) => {
+                writer.try_finish(header)?;
+                io::stdout().flush()
+            }
+            Self::Sequence(writer) => writer.finish(),
+        }
+    }
+}
+
+fn writer(args: &ExtractArgs) -> Result<AlignmentWriter> {
+    // multithreaded BGZF compression always uses the default level
+    if args.compression_level.is_some()
+        && args.threads.get() > 1
+        && args.sequence_format().is_none()
+        && args.output_format()? == OutputFormat::Bam
+    {
+        bail!("-l/--level cannot be combined with more than one thread (-t) for BAM output");
+    }
+
+    let file: Sink = match &args.output {
+        Some(file) => Box::new(File::create(file)?),
+        None => Box::new(io::stdout()),
     };
 
-    let writer: Box<dyn sam::alignment::io::Write> = if is_bam {
-        Box::new(bam::io::Writer::new(sink))
-    } else {
-        Box::new(sam::io::Writer::new(sink))
+    let buffered = |file: Sink| -> Sink { Box::new(io::BufWriter::new(file)) };
+
+    if let Some(to) = args.sequence_format() {
+        return Ok(AlignmentWriter::Sequence(SequenceWriter::new(
+            buffered(file),
+            to,
+            args.keep_soft_clips,
+        )));
+    }
+
+    let format = args.output_format()?;
+
+    if format != OutputFormat::Bam && (args.compression_level.is_some() || args.threads.get() > 1) {
+        warn!("compression level and threads only apply to BAM output");
+    }
+
+    let writer = match format {
+        OutputFormat::Sam => AlignmentWriter::Sam(sam::io::Writer::new(buffered(file))),
+        OutputFormat::Bam if args.threads.get() > 1 => {
+            // the compressed blocks are written whole, so the file is not buffered
+            AlignmentWriter::MultithreadedBam(bam::io::Writer::from(
+                bgzf::MultithreadedWriter::with_worker_count(args.threads, file),
+            ))
+        }
+        OutputFormat::Bam => {
+            let mut builder = bgzf::writer::Builder::default();
+            if let Some(level) = args.compression_level {
+                builder = builder.set_compression_level(level.try_into()?);
+            }
+            AlignmentWriter::Bam(bam::io::Writer::from(
+                builder.build_with_writer(buffered(file)),
+            ))
+        }
+        OutputFormat::Cram => {
+            let reference = args
+                .reference
+                .as_ref()
+                .context("CRAM output requires a reference FASTA (-T)")?;
+            let repository = fasta::indexed_reader::Builder::default()
+                .build_from_path(reference)
+                .map(fasta::repository::adapters::IndexedReader::new)
+                .map(fasta::Repository::new)
+                .with_context(|| format!("failed to open indexed FASTA {}", reference.display()))?;
+            // CRAM writers buffer whole containers, which cannot be flushed once written
+            AlignmentWriter::Cram(
+                cram::io::writer::Builder::default()
+                    .set_reference_sequence_repository(repository)
+                    .build_with_writer(file),
+            )
+        }
     };
 
     Ok(writer)
@@ -42,13 +458,15 @@ where
 
     for result in reader.lines() {
         let read_name = result.map(|s| s.trim().as_bytes().to_vec())?;
-        read_names.insert(read_name);
+        if !read_name.is_empty() {
+            read_names.insert(read_name);
+        }
     }
 
     Ok(read_names)
 }
 
-fn parse_read_ids(read_ids: &str) -> Result<HashSet<Vec<u8>>> {
+pub fn parse_read_ids(read_ids: &str) -> Result<HashSet<Vec<u8>>> {
     if read_ids.is_empty() {
         return Ok(HashSet::new());
     }
@@ -61,49 +479,725 @@ fn parse_read_ids(read_ids: &str) -> Result<HashSet<Vec<u8>>> {
 
     read_ids
         .split(',')
+        .filter(|id| !id.trim().is_empty())
         .map(|id| Ok(id.trim().as_bytes().to_vec())) // Replace with the actual method to create ReadName from &str
         .collect::<Result<HashSet<_>, _>>() // Assuming
 }
 
-pub fn extract<P>(read_ids: &str, bam_file: P, is_bam: bool) -> Result<()>
+fn parse_read_patterns(read_ids: &str) -> Result<Vec<String>> {
+    if let Ok(read_ids_path) = Path::new(read_ids).canonicalize() {
+        if read_ids_path.exists() {
+            return read_read_names_from_file(read_ids)?
+                .into_iter()
+                .map(|pattern| Ok(String::from_utf8(pattern)?))
+                .collect();
+        }
+    }
+
+    Ok(vec![read_ids.to_string()])
+}
+
+enum NamePatterns {
+    Any,
+    Exact(HashSet<Vec<u8>>),
+    Prefix(Vec<Vec<u8>>),
+    Regex(RegexSet),
+}
+
+/// Selects records by read name, either by exact IDs, name prefixes or regexes.
+struct NameMatcher {
+    patterns: NamePatterns,
+    invert: bool,
+}
+
+impl NameMatcher {
+    fn new(args: &ExtractArgs) -> Result<Self> {
+        let patterns = if args.readids.is_empty() {
+            NamePatterns::Any
+        } else if args.regex {
+            NamePatterns::Regex(RegexSet::new(parse_read_patterns(&args.readids)?)?)
+        } else if args.prefix {
+            NamePatterns::Prefix(parse_read_ids(&args.readids)?.into_iter().collect())
+        } else {
+            NamePatterns::Exact(parse_read_ids(&args.readids)?)
+        };
+
+        Ok(Self {
+            patterns,
+            invert: args.invert,
+        })
+    }
+
+    /// The requested read IDs when selecting exact names, which is when missing IDs can be reported.
+    fn read_ids(&self) -> Option<&HashSet<Vec<u8>>> {
+        match &self.patterns {
+            NamePatterns::Exact(ids) if !self.invert => Some(ids),
+            _ => None,
+        }
+    }
+
+    fn is_match(&self, name: Option<&[u8]>) -> bool {
+        let matched = match (&self.patterns, name) {
+            (NamePatterns::Any, _) => return true,
+            (_, None) => false,
+            (NamePatterns::Exact(ids), Some(name)) => ids.contains(name),
+            (NamePatterns::Prefix(prefixes), Some(name)) => {
+                prefixes.iter().any(|prefix| name.starts_with(prefix))
+            }
+            (NamePatterns::Regex(set), Some(name)) => set.is_match(name),
+        };
+
+        matched != self.invert
+    }
+}
+
+/// The records of a requested read ID in the input, and how many of them were written.
+#[derive(Clone, Copy, Debug, Default)]
+struct IdCount {
+    found: u64,
+    written: u64,
+}
+
+/// Counts the records found and written for each requested read ID.
+struct ReadIdReport {
+    counts: HashMap<Vec<u8>, IdCount>,
+}
+
+impl ReadIdReport {
+    fn new(matcher: &NameMatcher) -> Option<Self> {
+        let counts = matcher
+            .read_ids()?
+            .iter()
+            .map(|id| (id.clone(), IdCount::default()))
+            .collect();
+        Some(Self { counts })
+    }
+
+    fn count(&mut self, record: &bam::Record) -> Option<&mut IdCount> {
+        let name = record.name()?;
+        self.counts.get_mut(name.as_bytes())
+    }
+
+    fn sorted_counts(&self) -> Vec<(&[u8], IdCount)> {
+        let mut counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(id, count)| (id.as_slice(), *count))
+            .collect();
+        counts.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        counts
+    }
+
+    fn finish(&self, args: &ExtractArgs) -> Result<()> {
+        let counts = self.sorted_counts();
+        let missing: Vec<&[u8]> = counts
+            .iter()
+            .filter(|(_, count)| count.found == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        let filtered_out = counts
+            .iter()
+            .filter(|(_, count)| count.found > 0 && count.written == 0)
+            .count();
+        let records: u64 = counts.iter().map(|(_, count)| count.written).sum();
+
+        for (id, count) in &counts {
+            info!(
+                "{}\t{}\t{}",
+                String::from_utf8_lossy(id),
+                count.written,
+                count.found - count.written
+            );
+        }
+
+        eprintln!(
+            "requested {} read IDs, found {}, missing {}, filtered out {}, wrote {} records",
+            counts.len(),
+            counts.len() - missing.len(),
+            missing.len(),
+            filtered_out,
+            records
+        );
+
+        if let Some(summary) = &args.summary {
+            let mut writer = io::BufWriter::new(File::create(summary)?);
+            writeln!(writer, "read_id\trecords\tfiltered")?;
+            for (id, count) in &counts {
+                writer.write_all(id)?;
+                writeln!(
+                    writer,
+                    "\t{}\t{}",
+                    count.written,
+                    count.found - count.written
+                )?;
+            }
+            writer.flush()?;
+        }
+
+        if let Some(dst) = &args.missing {
+            let mut writer = io::BufWriter::new(File::create(dst)?);
+            for id in &missing {
+                writer.write_all(id)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+        }
+
+        if args.fail_on_missing && !missing.is_empty() {
+            bail!(
+                "{} read IDs were not found in {}",
+                missing.len(),
+                args.input.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Read regions from a BED file. BED intervals are 0-based and half-open,
+/// so they are shifted to the 1-based closed intervals used by noodles.
+fn read_bed_regions<P>(src: P) -> Result<Vec<Region>>
 where
-    P: AsRef<std::path::Path>,
+    P: AsRef<Path>,
 {
-    let read_names = parse_read_ids(read_ids)?;
+    let reader = File::open(src.as_ref()).map(BufReader::new)?;
+    let mut regions = Vec::new();
 
-    let mut reader = bam::io::reader::Builder.build_from_path(&bam_file)?;
-    let mut header = reader.read_header()?;
+    for (i, result) in reader.lines().enumerate() {
+        let line = result?;
+
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            bail!("invalid BED record at {}:{}", src.as_ref().display(), i + 1);
+        }
+
+        let start: usize = fields[1].parse()?;
+        let end: usize = fields[2].parse()?;
+
+        if end <= start {
+            bail!(
+                "invalid BED interval {}:{}-{} at line {}",
+                fields[0],
+                start,
+                end,
+                i + 1
+            );
+        }
 
-    let program = Map::<Program>::builder()
+        let start = Position::try_from(start + 1)?;
+        let end = Position::try_from(end)?;
+        regions.push(Region::new(fields[0], start..=end));
+    }
+
+    Ok(regions)
+}
+
+/// Read the index next to the BAM, trying `<src>.bai` before `<src>.csi`.
+fn read_index<P: AsRef<Path>>(src: P) -> io::Result<Box<dyn BinningIndex>> {
+    let src = src.as_ref().as_os_str();
+
+    let mut bai_src = src.to_os_string();
+    bai_src.push(".bai");
+
+    match bai::read(&bai_src) {
+        Ok(index) => Ok(Box::new(index)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut csi_src = src.to_os_string();
+            csi_src.push(".csi");
+            Ok(Box::new(csi::read(csi_src)?))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Sort regions in header order and merge overlapping ones, so that the
+/// output stays coordinate-sorted and each region is queried only once.
+fn merge_regions(header: &sam::Header, regions: Vec<Region>) -> Result<Vec<Region>> {
+    let mut intervals = Vec::with_capacity(regions.len());
+
+    for region in regions {
+        let id = header
+            .reference_sequences()
+            .get_index_of(region.name())
+            .with_context(|| {
+                format!(
+                    "reference sequence {} is not in the header",
+                    String::from_utf8_lossy(region.name())
+                )
+            })?;
+
+        let interval = region.interval();
+        let start = interval.start().unwrap_or(Position::MIN);
+        intervals.push((id, start, interval.end()));
+    }
+
+    intervals.sort_by_key(|(id, start, _)| (*id, *start));
+
+    let mut merged: Vec<(usize, Position, Option<Position>)> = Vec::new();
+
+    for (id, start, end) in intervals {
+        if let Some(last) = merged.last_mut() {
+            let overlaps = match last.2 {
+                Some(last_end) => start <= last_end,
+                None => true,
+            };
+
+            if last.0 == id && overlaps {
+                last.2 = match (last.2, end) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+                continue;
+            }
+        }
+        merged.push((id, start, end));
+    }
+
+    Ok(merged
+        .into_iter()
+        .map(|(id, start, end)| {
+            let (name, _) = header.reference_sequences().get_index(id).unwrap();
+            match end {
+                Some(end) => Region::new(name.to_vec(), start..=end),
+                None => Region::new(name.to_vec(), start..),
+            }
+        })
+        .collect())
+}
+
+fn parse_flags(s: &str) -> Result<u16, String> {
+    let flags = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    flags.map_err(|e| format!("invalid flags {s}: {e}"))
+}
+
+/// A data field that must be present, and equal to `value` if one is given.
+#[derive(Clone, Debug)]
+pub struct TagFilter {
+    tag: Tag,
+    value: Option<String>,
+}
+
+impl FromStr for TagFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tag, value) = match s.split_once(':') {
+            Some((tag, value)) => (tag, Some(value.to_string())),
+            None => (s, None),
+        };
+
+        match tag.as_bytes() {
+            [a, b] => Ok(Self {
+                tag: Tag::new(*a, *b),
+                value,
+            }),
+            _ => Err(format!("invalid tag {tag}, expected two characters")),
+        }
+    }
+}
+
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = self.tag.as_ref();
+        write!(f, "{}{}", char::from(tag[0]), char::from(tag[1]))?;
+
+        if let Some(value) = &self.value {
+            write!(f, ":{value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TagFilter {
+    fn is_match(&self, data: &bam::record::Data<'_>) -> io::Result<bool> {
+        let value = match data.get(&self.tag).transpose()? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+
+        let expected = match &self.value {
+            Some(expected) => expected,
+            None => return Ok(true),
+        };
+
+        let matched = match value {
+            Value::Character(c) => expected.as_bytes() == [c],
+            Value::Float(n) => expected.parse::<f32>() == Ok(n),
+            Value::String(s) | Value::Hex(s) => expected.as_bytes() == s.as_ref() as &[u8],
+            Value::Array(_) => false,
+            _ => match (value.as_int(), expected.parse::<i64>()) {
+                (Some(n), Ok(e)) => n == e,
+                _ => false,
+            },
+        };
+
+        Ok(matched)
+    }
+}
+
+/// Filters records on SAM flags, mapping quality and data fields.
+struct RecordFilter {
+    require_flags: u16,
+    exclude_flags: u16,
+    min_mapq: u8,
+    tags: Vec<TagFilter>,
+}
+
+impl RecordFilter {
+    fn new(args: &ExtractArgs) -> Self {
+        let mut require_flags = args.require_flags;
+        // a read is written once as FASTQ/FASTA, not once per alignment
+        let mut exclude_flags = match args.exclude_flags {
+            Some(flags) => flags,
+            None if args.sequence_format().is_some() && args.supplementary_only => SECONDARY,
+            None if args.sequence_format().is_some() => SECONDARY | SUPPLEMENTARY,
+            None => 0,
+        };
+
+        if args.primary_only {
+            exclude_flags |= SECONDARY | SUPPLEMENTARY;
+        }
+
+        if args.supplementary_only {
+            require_flags |= SUPPLEMENTARY;
+        }
+
+        Self {
+            require_flags,
+            exclude_flags,
+            min_mapq: args.min_mapq,
+            tags: args.tags.clone(),
+        }
+    }
+
+    fn is_match(&self, record: &bam::Record) -> io::Result<bool> {
+        let flags = u16::from(record.flags());
+
+        if flags & self.require_flags != self.require_flags || flags & self.exclude_flags != 0 {
+            return Ok(false);
+        }
+
+        // A missing mapping quality is stored as 255 and passes, as in samtools.
+        let mapq = record.mapping_quality().map(u8::from).unwrap_or(u8::MAX);
+        if mapq < self.min_mapq {
+            return Ok(false);
+        }
+
+        let data = record.data();
+        for tag in &self.tags {
+            if !tag.is_match(&data)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// The effective filter, recorded in the `@PG` command line.
+impl fmt::Display for RecordFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.require_flags != 0 {
+            write!(f, " -f {:#x}", self.require_flags)?;
+        }
+
+        if self.exclude_flags != 0 {
+            write!(f, " -F {:#x}", self.exclude_flags)?;
+        }
+
+        if self.min_mapq != 0 {
+            write!(f, " --min-mapq {}", self.min_mapq)?;
+        }
+
+        for tag in &self.tags {
+            write!(f, " --tag {tag}")?;
+        }
+
+        Ok(())
+    }
+}
+
+const SECONDARY: u16 = 0x100;
+const SUPPLEMENTARY: u16 = 0x800;
+
+/// Whether a record is written. Records of a requested read ID are counted as found before the
+/// record filter, so an ID whose records are all filtered out is not reported as missing.
+fn is_selected(
+    record: &bam::Record,
+    matcher: &NameMatcher,
+    filter: &RecordFilter,
+    report: &mut Option<ReadIdReport>,
+) -> io::Result<bool> {
+    let name = record.name();
+    if !matcher.is_match(name.as_ref().map(|name| name.as_bytes())) {
+        return Ok(false);
+    }
+
+    let count = report.as_mut().and_then(|report| report.count(record));
+    if !filter.is_match(record)? {
+        if let Some(count) = count {
+            count.found += 1;
+        }
+        return Ok(false);
+    }
+
+    if let Some(count) = count {
+        count.found += 1;
+        count.written += 1;
+    }
+    Ok(true)
+}
+
+fn command_line(args: &ExtractArgs, filter: &RecordFilter) -> String {
+    let mut command_line = format!(
+        "rboss extract {} {}",
+        if args.readids.is_empty() {
+            "\"\""
+        } else {
+            &args.readids
+        },
+        args.input.to_string_lossy(),
+    );
+
+    if args.isbam {
+        command_line.push_str(" -b");
+    }
+
+    if args.invert {
+        command_line.push_str(" --invert");
+    }
+
+    if args.regex {
+        command_line.push_str(" --regex");
+    }
+
+    if args.prefix {
+        command_line.push_str(" --prefix");
+    }
+
+    command_line.push_str(&filter.to_string());
+
+    if let Some(output) = &args.output {
+        command_line.push_str(&format!(" -o {}", output.to_string_lossy()));
+    }
+
+    if let Some(format) = args.format {
+        let format = format.to_possible_value().unwrap();
+        command_line.push_str(&format!(" --format {}", format.get_name()));
+    }
+
+    if let Some(reference) = &args.reference {
+        command_line.push_str(&format!(" -T {}", reference.to_string_lossy()));
+    }
+
+    if let Some(level) = args.compression_level {
+        command_line.push_str(&format!(" -l {}", level));
+    }
+
+    if let Some(to) = args.to {
+        let to = to.to_possible_value().unwrap();
+        command_line.push_str(&format!(" --to {}", to.get_name()));
+    }
+
+    if args.keep_soft_clips {
+        command_line.push_str(" --keep-soft-clips");
+    }
+
+    for region in &args.regions {
+        command_line.push_str(&format!(" -r {}", region));
+    }
+
+    if let Some(bed) = &args.bed {
+        command_line.push_str(&format!(" -L {}", bed.to_string_lossy()));
+    }
+
+    command_line
+}
+
+/// Adds an `rboss` `@PG` line with the command line. Earlier `rboss` lines are kept:
+/// the new one gets a unique ID and follows the last program in the chain.
+pub fn add_program(header: &mut sam::Header, command_line: &str) -> Result<()> {
+    let programs = header.programs_mut();
+
+    let mut id = BString::from("rboss");
+    let mut n = 0;
+    while programs.contains_key(&id) {
+        n += 1;
+        id = BString::from(format!("rboss.{n}"));
+    }
+
+    let mut builder = Map::<Program>::builder()
         .insert(program::tag::NAME, Vec::from("rboss"))
         .insert(program::tag::VERSION, Vec::from(crate_version!()))
-        .insert(
-            program::tag::COMMAND_LINE,
-            Vec::from(format!(
-                "rboss extract {} {} {}",
-                read_ids,
-                bam_file.as_ref().to_string_lossy(),
-                if is_bam { "-b" } else { "" }
-            )),
-        )
-        .build()?;
-
-    header
-        .programs_mut()
-        .insert(BString::from("rboss"), program);
-
-    let mut writer = writer(None, is_bam)?;
-
-    writer.write_alignment_header(&header)?;
-
-    for result in reader.records() {
-        let record = result?;
-        if let Some(read_name) = record.name() {
-            if read_names.contains(read_name.as_bytes()) {
-                writer.write_alignment_record(&header, &record)?;
+        .insert(program::tag::COMMAND_LINE, Vec::from(command_line));
+
+    if let Some(previous) = programs.keys().last() {
+        builder = builder.insert(program::tag::PREVIOUS_PROGRAM_ID, previous.to_vec());
+    }
+
+    programs.insert(id, builder.build()?);
+
+    Ok(())
+}
+
+pub fn extract(args: &ExtractArgs) -> Result<()> {
+    let matcher = NameMatcher::new(args)?;
+    let filter = RecordFilter::new(args);
+    let mut report = ReadIdReport::new(&matcher);
+
+    let mut regions = args.regions.clone();
+    if let Some(bed) = &args.bed {
+        regions.extend(read_bed_regions(bed)?);
+    }
+
+    let mut reader = bgzf::reader::Builder::default()
+        .set_worker_count(args.threads)
+        .build_from_path(&args.input)
+        .map(bam::io::Reader::from)?;
+
+    let index = if regions.is_empty() {
+        None
+    } else {
+        let index = read_index(&args.input).with_context(|| {
+            format!(
+                "failed to read the index of {}, run `rboss index` first",
+                args.input.display()
+            )
+        })?;
+        Some(index)
+    };
+
+    let mut header = reader.read_header()?;
+    let regions = match index {
+        Some(_) => merge_regions(&header, regions)?,
+        None => Vec::new(),
+    };
+    add_program(&mut header, &command_line(args, &filter))?;
+
+    // The output is only created once the input, its index and the regions are known
+    // to be usable, so a failed query does not leave an empty file behind.
+    let mut writer = writer(args)?;
+    writer.write_header(&header)?;
+
+    if let Some(index) = index {
+        let mut previous: Option<Region> = None;
+
+        for region in regions {
+            // Records overlapping the previous region on the same reference were already
+            // written, and those are exactly the ones starting before its end.
+            let written_until = previous
+                .as_ref()
+                .filter(|prev| prev.name() == region.name())
+                .and_then(|prev| prev.interval().end());
+
+            for result in reader.query(&header, &index, &region)? {
+                let record = result?;
+
+                if let (Some(end), Some(start)) = (written_until, record.alignment_start()) {
+                    if start? <= end {
+                        continue;
+                    }
+                }
+
+                if is_selected(&record, &matcher, &filter, &mut report)? {
+                    writer.write_record(&header, &record)?;
+                }
             }
+
+            previous = Some(region);
         }
+    } else {
+        for result in reader.records() {
+            let record = result?;
+            if is_selected(&record, &matcher, &filter, &mut report)? {
+                writer.write_record(&header, &record)?;
+            }
+        }
+    }
+
+    // The report comes after the output is complete, so a failure to write it is not hidden
+    // behind a report of IDs that were not written.
+    writer.finish(&header)?;
+
+    if let Some(report) = report {
+        report.finish(args)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sam::alignment::{
+        record::{cigar::Op, Flags},
+        record_buf::{data, QualityScores, Sequence},
+        RecordBuf,
+    };
+
+    fn record(flags: Flags) -> RecordBuf {
+        RecordBuf::builder()
+            .set_flags(flags)
+            .set_cigar(
+                [Op::new(Kind::SoftClip, 2), Op::new(Kind::Match, 4)]
+                    .into_iter()
+                    .collect(),
+            )
+            .set_sequence(Sequence::from(b"TTACGG".to_vec()))
+            .set_quality_scores(QualityScores::from(vec![1, 2, 3, 4, 5, 6]))
+            .build()
+    }
+
+    #[test]
+    fn test_original_read() {
+        let (sequence, qualities) = original_read(&record(Flags::empty()), true).unwrap();
+        assert_eq!(sequence, b"TTACGG");
+        assert_eq!(qualities, b"\"#$%&'");
+
+        let (sequence, qualities) = original_read(&record(Flags::empty()), false).unwrap();
+        assert_eq!(sequence, b"ACGG");
+        assert_eq!(qualities, b"$%&'");
+    }
+
+    #[test]
+    fn test_original_read_reverse() {
+        let reverse = Flags::REVERSE_COMPLEMENTED;
+
+        let (sequence, qualities) = original_read(&record(reverse), true).unwrap();
+        assert_eq!(sequence, b"CCGTAA");
+        assert_eq!(qualities, b"'&%$#\"");
+
+        let (sequence, qualities) = original_read(&record(reverse), false).unwrap();
+        assert_eq!(sequence, b"CCGT");
+        assert_eq!(qualities, b"'&%$");
+    }
+
+    #[test]
+    fn test_original_read_qualities() {
+        let mut record = record(Flags::empty());
+        *record.quality_scores_mut() = QualityScores::from(vec![1, 2, 3, 4, 5, 250]);
+        let (_, qualities) = original_read(&record, true).unwrap();
+        assert_eq!(qualities, b"\"#$%&~");
+
+        record.data_mut().insert(
+            Tag::ORIGINAL_QUALITY_SCORES,
+            data::field::Value::String("ABCDEF".into()),
+        );
+        let (_, qualities) = original_read(&record, false).unwrap();
+        assert_eq!(qualities, b"CDEF");
+    }
+}
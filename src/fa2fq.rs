@@ -1,24 +1,226 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueHint};
+use log::warn;
+use noodles_bam as bam;
 use noodles_fasta as fasta;
 use noodles_fastq as fastq;
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
 
-pub fn fa2fq<P: AsRef<Path>>(input: P) -> Result<()> {
-    let mut reader = fasta::reader::Builder.build_from_path(input)?;
-    let mut writer = fastq::Writer::new(std::io::stdout());
+use crate::compression;
+use crate::extract::{original_read, quality_scores};
+
+#[derive(Args, Debug)]
+pub struct Fa2fqArgs {
+    /// fasta input file, plain or compressed with gzip, bgzip or zstd; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Quality of every base, a character or a Phred score (e.g. I or 40)
+    #[arg(long = "quality", default_value = "@", value_parser = parse_quality)]
+    quality: u8,
+
+    /// Take per-base qualities from a .qual file or a BAM, matched by read name.
+    /// Reads that are not found or have no qualities in a BAM get the --quality value
+    #[arg(long = "qual-from", value_hint = ValueHint::FilePath)]
+    qual_from: Option<PathBuf>,
+}
+
+/// Parses a Phred score (0-93) or a printable quality character into Phred+33.
+fn parse_quality(s: &str) -> Result<u8, String> {
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return match s.parse::<u8>() {
+            Ok(q) if q <= 93 => Ok(q + 33),
+            _ => Err(format!("invalid Phred score {s}, expected 0-93")),
+        };
+    }
+
+    match s.as_bytes() {
+        [c @ b'!'..=b'~'] => Ok(*c),
+        _ => Err(format!(
+            "invalid quality {s}, expected a character or a Phred score"
+        )),
+    }
+}
+
+/// Reads Phred+33 qualities from a `.qual` file: FASTA-like records with
+/// whitespace-separated Phred scores.
+fn read_qual_file<P: AsRef<Path>>(src: P) -> Result<HashMap<Vec<u8>, Vec<u8>>> {
+    let reader = compression::open(src)?;
+    let mut qualities = HashMap::new();
+    let mut current: Option<(Vec<u8>, Vec<u8>)> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if let Some(definition) = line.strip_prefix('>') {
+            qualities.extend(current.take());
+            let name = definition.split_whitespace().next().unwrap_or_default();
+            current = Some((name.as_bytes().to_vec(), Vec::new()));
+        } else if let Some((name, scores)) = current.as_mut() {
+            for score in line.split_whitespace() {
+                match score.parse::<u8>() {
+                    Ok(q) if q <= 93 => scores.push(q + 33),
+                    _ => bail!(
+                        "invalid quality score {score} in {}",
+                        String::from_utf8_lossy(name)
+                    ),
+                }
+            }
+        } else if !line.trim().is_empty() {
+            bail!("expected a '>' line in the quality file");
+        }
+    }
+
+    qualities.extend(current);
+    Ok(qualities)
+}
+
+/// Reads the original qualities of the primary records of a BAM.
+fn read_bam_qualities<P: AsRef<Path>>(src: P) -> Result<HashMap<Vec<u8>, Vec<u8>>> {
+    let mut reader = bam::io::reader::Builder.build_from_path(src)?;
+    reader.read_header()?;
+
+    let mut qualities = HashMap::new();
+
+    for result in reader.records() {
+        let record = result?;
+        let flags = record.flags();
+
+        if flags.is_secondary() || flags.is_supplementary() {
+            continue;
+        }
+
+        // records without qualities are left out, so their reads get --quality
+        if let Some(name) = record.name().filter(|_| quality_scores(&record).is_some()) {
+            let (_, quality_scores) = original_read(&record, true)?;
+            qualities.insert(name.as_bytes().to_vec(), quality_scores);
+        }
+    }
+
+    Ok(qualities)
+}
+
+fn read_qualities<P: AsRef<Path>>(src: P) -> Result<HashMap<Vec<u8>, Vec<u8>>> {
+    let src = src.as_ref();
+
+    let qualities = match src.extension().and_then(|ext| ext.to_str()) {
+        Some("bam") => read_bam_qualities(src),
+        _ => read_qual_file(src),
+    };
+
+    qualities.with_context(|| format!("failed to read qualities from {}", src.display()))
+}
+
+pub fn fa2fq(args: &Fa2fqArgs) -> Result<()> {
+    let mut reader = compression::open(&args.input).map(fasta::Reader::new)?;
+    let mut output = compression::create(args.output.as_ref())?;
+    let mut writer = fastq::Writer::new(&mut output);
+
+    let mut qualities = match &args.qual_from {
+        Some(src) => Some(read_qualities(src)?),
+        None => None,
+    };
+    let mut missing = 0;
 
     for result in reader.records() {
         let record = result?;
         let name = record.name();
+        let description = record.description().unwrap_or_default();
         let sequence = record.sequence().as_ref().to_vec();
-        let qualities = vec![b'@'; sequence.len()];
+
+        let quality_scores = match qualities.as_mut().map(|q| q.remove(name)) {
+            Some(Some(quality_scores)) => {
+                if quality_scores.len() != sequence.len() {
+                    bail!(
+                        "{} has {} bases but {} qualities",
+                        String::from_utf8_lossy(name),
+                        sequence.len(),
+                        quality_scores.len()
+                    );
+                }
+                quality_scores
+            }
+            Some(None) => {
+                missing += 1;
+                vec![args.quality; sequence.len()]
+            }
+            None => vec![args.quality; sequence.len()],
+        };
+
         let fastq_record = fastq::Record::new(
-            fastq::record::Definition::new(name, ""),
+            fastq::record::Definition::new(name, description),
             sequence,
-            qualities,
+            quality_scores,
         );
         writer.write_record(&fastq_record)?;
     }
 
+    output.finish()?;
+
+    if missing > 0 {
+        warn!("{missing} reads have no qualities in the --qual-from file");
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(parse_quality("@"), Ok(b'@'));
+        assert_eq!(parse_quality("I"), Ok(b'I'));
+        assert_eq!(parse_quality("40"), Ok(b'I'));
+        assert_eq!(parse_quality("0"), Ok(b'!'));
+        assert!(parse_quality("94").is_err());
+        assert!(parse_quality("II").is_err());
+        assert!(parse_quality(" ").is_err());
+    }
+
+    #[test]
+    fn test_read_bam_qualities_without_qualities() {
+        use noodles_sam::{
+            self as sam,
+            alignment::{
+                io::Write,
+                record_buf::{QualityScores, Sequence},
+                RecordBuf,
+            },
+        };
+
+        let record = |name: &[u8], quality_scores: Vec<u8>| {
+            RecordBuf::builder()
+                .set_name(name.into())
+                .set_sequence(Sequence::from(b"ACGT".to_vec()))
+                .set_quality_scores(QualityScores::from(quality_scores))
+                .build()
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("reads.bam");
+        let header = sam::Header::default();
+        let mut writer = bam::io::Writer::new(std::fs::File::create(&src).unwrap());
+        writer.write_header(&header).unwrap();
+        for record in [
+            record(b"r1", vec![0, 10, 20, 30]),
+            // written as 0xff, the missing quality string
+            record(b"r2", Vec::new()),
+        ] {
+            writer.write_alignment_record(&header, &record).unwrap();
+        }
+        writer.try_finish().unwrap();
+        drop(writer);
+
+        let qualities = read_bam_qualities(&src).unwrap();
+        assert_eq!(qualities.len(), 1);
+        assert_eq!(qualities[&b"r1"[..]], b"!+5?");
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use clap::{Args, ValueHint};
+use log::info;
+use noodles_fasta as fasta;
+use noodles_fastq as fastq;
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+
+use crate::compression;
+use crate::extract::{parse_read_ids, SequenceFormat};
+
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// FASTQ or FASTA input file, plain or compressed; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Output format [default: the input format]
+    #[arg(long = "to", value_enum)]
+    to: Option<SequenceFormat>,
+
+    /// Minimum read length after trimming
+    #[arg(short = 'l', long = "min-length", default_value = "1")]
+    min_length: usize,
+
+    /// Maximum read length after trimming
+    #[arg(short = 'L', long = "max-length")]
+    max_length: Option<usize>,
+
+    /// Minimum mean Phred quality of a read after trimming, FASTQ only
+    #[arg(long = "min-qual")]
+    min_qual: Option<f64>,
+
+    /// Maximum fraction of N bases in a read after trimming (0-1)
+    #[arg(long = "max-n", value_parser = parse_fraction)]
+    max_n: Option<f64>,
+
+    /// Only keep these reads, a comma-separated list or a file with one ID per line
+    #[arg(long = "include", value_hint = ValueHint::FilePath)]
+    include: Option<String>,
+
+    /// Drop these reads, a comma-separated list or a file with one ID per line
+    #[arg(long = "exclude", value_hint = ValueHint::FilePath)]
+    exclude: Option<String>,
+
+    /// Number of bases to trim from the start of every read
+    #[arg(long = "trim-head", default_value = "0")]
+    trim_head: usize,
+
+    /// Number of bases to trim from the end of every read
+    #[arg(long = "trim-tail", default_value = "0")]
+    trim_tail: usize,
+}
+
+pub fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        _ => Err(format!("invalid fraction {s}, expected 0-1")),
+    }
+}
+
+fn mean_quality(quality_scores: &[u8]) -> f64 {
+    let sum: u64 = quality_scores
+        .iter()
+        .map(|&q| u64::from(q.saturating_sub(b'!')))
+        .sum();
+    sum as f64 / quality_scores.len() as f64
+}
+
+fn n_fraction(sequence: &[u8]) -> f64 {
+    let n = sequence.iter().filter(|&&b| b == b'N' || b == b'n').count();
+    n as f64 / sequence.len() as f64
+}
+
+/// Decides which reads are kept, after trimming.
+struct ReadFilter {
+    min_length: usize,
+    max_length: usize,
+    min_qual: Option<f64>,
+    max_n: Option<f64>,
+    include: Option<HashSet<Vec<u8>>>,
+    exclude: HashSet<Vec<u8>>,
+    trim_head: usize,
+    trim_tail: usize,
+}
+
+impl ReadFilter {
+    fn new(args: &FilterArgs) -> Result<Self> {
+        Ok(Self {
+            min_length: args.min_length,
+            max_length: args.max_length.unwrap_or(usize::MAX),
+            min_qual: args.min_qual,
+            max_n: args.max_n,
+            include: args.include.as_deref().map(parse_read_ids).transpose()?,
+            exclude: match &args.exclude {
+                Some(read_ids) => parse_read_ids(read_ids)?,
+                None => HashSet::new(),
+            },
+            trim_head: args.trim_head,
+            trim_tail: args.trim_tail,
+        })
+    }
+
+    /// The kept part of a read, or `None` if the read is dropped.
+    fn apply(&self, record: &fastq::Record) -> Option<fastq::Record> {
+        let name = record.name();
+        if self.exclude.contains(name) {
+            return None;
+        }
+        if matches!(&self.include, Some(include) if !include.contains(name)) {
+            return None;
+        }
+
+        let len = record.sequence().len();
+        let end = len.saturating_sub(self.trim_tail);
+        let start = self.trim_head.min(end);
+
+        if !(self.min_length..=self.max_length).contains(&(end - start)) {
+            return None;
+        }
+
+        let sequence = &record.sequence()[start..end];
+        // FASTA reads have no qualities
+        let quality_scores = record.quality_scores().get(start..end).unwrap_or_default();
+
+        if matches!(self.max_n, Some(max_n) if n_fraction(sequence) > max_n) {
+            return None;
+        }
+        if matches!(self.min_qual, Some(min_qual) if mean_quality(quality_scores) < min_qual) {
+            return None;
+        }
+
+        Some(fastq::Record::new(
+            fastq::record::Definition::new(name, record.description()),
+            sequence,
+            quality_scores,
+        ))
+    }
+}
+
+/// Calls `f` with every read of a FASTQ or FASTA stream. FASTA reads have no qualities.
+pub fn for_each_record<R, F>(reader: R, format: SequenceFormat, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(fastq::Record) -> io::Result<()>,
+{
+    match format {
+        SequenceFormat::Fastq => {
+            for result in fastq::Reader::new(reader).records() {
+                f(result?)?;
+            }
+        }
+        SequenceFormat::Fasta => {
+            for result in fasta::Reader::new(reader).records() {
+                let record = result?;
+                let definition = fastq::record::Definition::new(
+                    record.name(),
+                    record.description().unwrap_or_default(),
+                );
+                let sequence = record.sequence().as_ref().to_vec();
+                f(fastq::Record::new(definition, sequence, Vec::new()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes reads as FASTQ or FASTA.
+pub enum Writer<W: io::Write> {
+    Fastq(fastq::Writer<W>),
+    Fasta(fasta::Writer<W>),
+}
+
+impl<W: io::Write> Writer<W> {
+    pub fn new(inner: W, format: SequenceFormat) -> Self {
+        match format {
+            SequenceFormat::Fastq => Self::Fastq(fastq::Writer::new(inner)),
+            SequenceFormat::Fasta => Self::Fasta(fasta::Writer::new(inner)),
+        }
+    }
+
+    pub fn write_record(&mut self, record: &fastq::Record) -> io::Result<()> {
+        match self {
+            Self::Fastq(writer) => writer.write_record(record),
+            Self::Fasta(writer) => {
+                let description = Some(record.description())
+                    .filter(|description| !description.is_empty())
+                    .map(|description| description.to_vec());
+                let definition =
+                    fasta::record::Definition::new(record.name().to_vec(), description);
+                let sequence = fasta::record::Sequence::from(record.sequence().to_vec());
+                writer.write_record(&fasta::Record::new(definition, sequence))
+            }
+        }
+    }
+}
+
+pub fn filter(args: &FilterArgs) -> Result<()> {
+    let read_filter = ReadFilter::new(args)?;
+
+    let mut reader = compression::open(&args.input)?;
+    let input_format = match reader.fill_buf()?.first() {
+        Some(b'>') => SequenceFormat::Fasta,
+        Some(b'@') | None => SequenceFormat::Fastq,
+        Some(_) => bail!("{} is not a FASTQ or FASTA file", args.input.display()),
+    };
+
+    let output_format = args.to.unwrap_or(input_format);
+    if input_format == SequenceFormat::Fasta {
+        if output_format == SequenceFormat::Fastq {
+            bail!("FASTA input has no qualities to write as FASTQ, use `rboss fa2fq`");
+        }
+        if args.min_qual.is_some() {
+            bail!("--min-qual needs FASTQ input");
+        }
+    }
+
+    let mut output = compression::create(args.output.as_ref())?;
+    let mut writer = Writer::new(&mut output, output_format);
+    let (mut total, mut kept) = (0u64, 0u64);
+
+    for_each_record(reader, input_format, |record| {
+        total += 1;
+        if let Some(record) = read_filter.apply(&record) {
+            kept += 1;
+            writer.write_record(&record)?;
+        }
+        Ok(())
+    })?;
+
+    output.finish()?;
+    info!("kept {kept} of {total} reads");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_filter() -> ReadFilter {
+        ReadFilter {
+            min_length: 1,
+            max_length: usize::MAX,
+            min_qual: None,
+            max_n: None,
+            include: None,
+            exclude: HashSet::new(),
+            trim_head: 0,
+            trim_tail: 0,
+        }
+    }
+
+    fn record(sequence: &str, quality_scores: &str) -> fastq::Record {
+        fastq::Record::new(
+            fastq::record::Definition::new("r1", "desc"),
+            sequence,
+            quality_scores,
+        )
+    }
+
+    #[test]
+    fn test_trim() {
+        let read_filter = ReadFilter {
+            trim_head: 2,
+            trim_tail: 1,
+            ..read_filter()
+        };
+
+        let trimmed = read_filter.apply(&record("ACGTAC", "ABCDEF")).unwrap();
+        assert_eq!(trimmed.sequence(), b"GTA");
+        assert_eq!(trimmed.quality_scores(), b"CDE");
+        assert_eq!(trimmed.description(), b"desc");
+
+        // nothing left after trimming
+        assert!(read_filter.apply(&record("ACG", "ABC")).is_none());
+    }
+
+    #[test]
+    fn test_quality_and_n() {
+        let read_filter = ReadFilter {
+            min_qual: Some(20.0),
+            max_n: Some(0.25),
+            ..read_filter()
+        };
+
+        // mean of Q10 and Q30
+        assert!(read_filter.apply(&record("ACGT", "++??")).is_some());
+        assert!(read_filter.apply(&record("ACGT", "++?+")).is_none());
+        assert!(read_filter.apply(&record("ACNN", "????")).is_none());
+        assert!(read_filter.apply(&record("ACGN", "????")).is_some());
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{bail, Context, Result};
+use bstr::BString;
+use clap::{Args, ValueHint};
+use log::info;
+use noodles_fastq as fastq;
+use noodles_sam::{
+    self as sam,
+    alignment::{
+        record::data::field::Tag,
+        record_buf::{
+            data::field::{value::Array, Value},
+            Data, QualityScores, Sequence,
+        },
+        RecordBuf,
+    },
+    header::record::value::{
+        map::{self, read_group, ReadGroup},
+        Map,
+    },
+};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::compression;
+use crate::extract::add_program;
+use crate::sort;
+
+#[derive(Args, Debug)]
+pub struct Fq2bamArgs {
+    /// FASTQ input file, plain or compressed; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// FASTQ file of the second reads of pairs, in the same order as the input
+    #[arg(value_hint = ValueHint::FilePath)]
+    input2: Option<PathBuf>,
+
+    /// Output BAM file [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Read group ID, added to the header and to every read
+    #[arg(short = 'R', long = "rg-id")]
+    rg_id: Option<String>,
+
+    /// Read group field as TAG:VALUE (e.g. SM:sample1 or PL:ONT), can be repeated
+    #[arg(short = 'r', long = "rg", requires = "rg_id", value_parser = parse_rg_field)]
+    rg_fields: Vec<([u8; 2], String)>,
+
+    /// BGZF compression level (0-9)
+    #[arg(short = 'l', long = "level", value_parser = clap::value_parser!(u8).range(0..=9))]
+    compression_level: Option<u8>,
+
+    /// Number of threads to compress the output
+    #[arg(short = 't', long = "threads", default_value = "1")]
+    threads: NonZeroUsize,
+}
+
+fn parse_rg_field(s: &str) -> Result<([u8; 2], String), String> {
+    match s.split_once(':') {
+        Some((tag, value)) if tag.len() == 2 && tag != "ID" => {
+            let [a, b] = <[u8; 2]>::try_from(tag.as_bytes()).unwrap();
+            Ok(([a, b], value.to_string()))
+        }
+        _ => Err(format!(
+            "invalid read group field {s}, expected TAG:VALUE with a tag other than ID"
+        )),
+    }
+}
+
+fn parse_values<N: FromStr>(values: &str) -> Option<Vec<N>> {
+    if values.is_empty() {
+        return Some(Vec::new());
+    }
+    values.split(',').map(|value| value.parse().ok()).collect()
+}
+
+fn parse_array(s: &str) -> Option<Value> {
+    let (subtype, values) = s.split_once(',').unwrap_or((s, ""));
+
+    let array = match subtype {
+        "c" => Array::Int8(parse_values(values)?),
+        "C" => Array::UInt8(parse_values(values)?),
+        "s" => Array::Int16(parse_values(values)?),
+        "S" => Array::UInt16(parse_values(values)?),
+        "i" => Array::Int32(parse_values(values)?),
+        "I" => Array::UInt32(parse_values(values)?),
+        "f" => Array::Float(parse_values(values)?),
+        _ => return None,
+    };
+
+    Some(Value::Array(array))
+}
+
+/// Parses a `TAG:TYPE:VALUE` comment token, as written by `bam2fq -T` or `samtools fastq -T`.
+fn parse_field(token: &[u8]) -> Option<(Tag, Value)> {
+    let [a, b, b':', ty, b':', value @ ..] = token else {
+        return None;
+    };
+    if !a.is_ascii_alphabetic() || !b.is_ascii_alphanumeric() {
+        return None;
+    }
+    let value = std::str::from_utf8(value).ok()?;
+
+    let value = match ty {
+        b'A' => match value.as_bytes() {
+            [c] => Value::Character(*c),
+            _ => return None,
+        },
+        b'i' => {
+            let n: i64 = value.parse().ok()?;
+            i32::try_from(n)
+                .map(Value::Int32)
+                .or_else(|_| u32::try_from(n).map(Value::UInt32))
+                .ok()?
+        }
+        b'f' => Value::Float(value.parse().ok()?),
+        b'Z' => Value::String(value.into()),
+        b'H' => Value::Hex(value.into()),
+        b'B' => parse_array(value)?,
+        _ => return None,
+    };
+
+    Some((Tag::new(*a, *b), value))
+}
+
+/// Data fields from the comment tokens, other tokens are ignored.
+fn parse_comment(comment: &[u8]) -> Vec<(Tag, Value)> {
+    comment
+        .split(|b| b.is_ascii_whitespace())
+        .filter_map(parse_field)
+        .collect()
+}
+
+/// Strips the `/1` or `/2` of paired read names.
+fn template_name(name: &[u8]) -> &[u8] {
+    match name {
+        [name @ .., b'/', b'1' | b'2'] => name,
+        _ => name,
+    }
+}
+
+fn unaligned_record(
+    record: &fastq::Record,
+    name: &[u8],
+    flags: sam::alignment::record::Flags,
+    read_group: Option<&str>,
+) -> RecordBuf {
+    let mut fields = parse_comment(record.description());
+    if let Some(id) = read_group {
+        if !fields.iter().any(|(tag, _)| *tag == Tag::READ_GROUP) {
+            fields.push((Tag::READ_GROUP, Value::from(id)));
+        }
+    }
+
+    let quality_scores = record
+        .quality_scores()
+        .iter()
+        .map(|q| q.saturating_sub(b'!'))
+        .collect::<Vec<_>>();
+
+    RecordBuf::builder()
+        .set_name(name.into())
+        .set_flags(flags)
+        .set_sequence(Sequence::from(record.sequence()))
+        .set_quality_scores(QualityScores::from(quality_scores))
+        .set_data(fields.into_iter().collect::<Data>())
+        .build()
+}
+
+fn header(args: &Fq2bamArgs) -> Result<sam::Header> {
+    let mut builder = sam::Header::builder().set_header(Map::<map::Header>::default());
+
+    if let Some(id) = &args.rg_id {
+        let mut read_group = Map::<ReadGroup>::builder();
+        for (tag, value) in &args.rg_fields {
+            // every read group tag but ID is an "other" tag
+            if let map::tag::Tag::Other(tag) =
+                map::tag::Tag::<read_group::tag::Standard>::from(*tag)
+            {
+                read_group = read_group.insert(tag, value.clone());
+            }
+        }
+        builder = builder.add_read_group(id.as_str(), read_group.build()?);
+    }
+
+    let mut header = builder.build();
+    let command_line = std::env::args().collect::<Vec<_>>().join(" ");
+    add_program(&mut header, &command_line)?;
+
+    Ok(header)
+}
+
+pub fn fq2bam(args: &Fq2bamArgs) -> Result<()> {
+    use sam::alignment::record::Flags;
+
+    let header = header(args)?;
+    let read_group = args.rg_id.as_deref();
+
+    let mut writer =
+        sort::bam_writer(args.output.as_deref(), args.threads, args.compression_level)?;
+    writer.write_header(&header)?;
+
+    let open = |src: &PathBuf| {
+        compression::open(src)
+            .map(fastq::Reader::new)
+            .with_context(|| format!("failed to open {}", src.display()))
+    };
+    let mut reader1 = open(&args.input)?;
+    let mut records1 = reader1.records();
+    let mut count = 0;
+
+    match &args.input2 {
+        None => {
+            for result in records1 {
+                let record = result?;
+                let record = unaligned_record(&record, record.name(), Flags::UNMAPPED, read_group);
+                writer.write_record(&header, &record)?;
+                count += 1;
+            }
+        }
+        Some(input2) => {
+            let paired = Flags::SEGMENTED | Flags::UNMAPPED | Flags::MATE_UNMAPPED;
+            let mut reader2 = open(input2)?;
+            let mut records2 = reader2.records();
+
+            loop {
+                let (r1, r2) = match (records1.next().transpose()?, records2.next().transpose()?) {
+                    (Some(r1), Some(r2)) => (r1, r2),
+                    (None, None) => break,
+                    _ => bail!("the two FASTQ files have a different number of reads"),
+                };
+
+                let name = template_name(r1.name());
+                if name != template_name(r2.name()) {
+                    bail!(
+                        "read {} is paired with {}, the FASTQ files are not in the same order",
+                        BString::from(r1.name()),
+                        BString::from(r2.name())
+                    );
+                }
+
+                for (record, flag) in [(&r1, Flags::FIRST_SEGMENT), (&r2, Flags::LAST_SEGMENT)] {
+                    let record = unaligned_record(record, name, paired | flag, read_group);
+                    writer.write_record(&header, &record)?;
+                }
+                count += 1;
+            }
+        }
+    }
+
+    writer.finish(&header)?;
+    info!("wrote {count} reads or pairs");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comment() {
+        let fields = parse_comment(b"1:N:0:ACGT CB:Z:AAAC\tML:B:C,1,200 HP:i:2 np:i:-3 bad:x:1");
+        assert_eq!(
+            fields,
+            [
+                (Tag::new(b'C', b'B'), Value::from("AAAC")),
+                (
+                    Tag::new(b'M', b'L'),
+                    Value::Array(Array::UInt8(vec![1, 200]))
+                ),
+                (Tag::new(b'H', b'P'), Value::Int32(2)),
+                (Tag::new(b'n', b'p'), Value::Int32(-3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_name() {
+        assert_eq!(template_name(b"r1/1"), b"r1");
+        assert_eq!(template_name(b"r1/2"), b"r1");
+        assert_eq!(template_name(b"r1"), b"r1");
+    }
+}
@@ -1,26 +1,51 @@
 use anyhow::Result;
+use clap::{Args, ValueHint};
 use noodles_fasta as fasta;
 use noodles_fastq as fastq;
-use std::path::Path;
-use std::{
-    fs::File,
-    io::{self, BufReader},
-};
+use std::path::PathBuf;
 
-pub fn fq2fa<P: AsRef<Path>>(input: P) -> Result<()> {
-    let mut readerr = File::open(input)
-        .map(BufReader::new)
-        .map(fastq::Reader::new)?;
+use crate::compression;
 
-    let mut writer = fasta::Writer::new(io::stdout());
+#[derive(Args, Debug)]
+pub struct Fq2faArgs {
+    /// fastq input file, plain or compressed with gzip, bgzip or zstd; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
 
-    for result in readerr.records() {
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Number of bases per line of the FASTA output, 0 for no wrapping
+    #[arg(short = 'w', long = "line-width", default_value = "80")]
+    line_width: usize,
+}
+
+pub fn fq2fa(args: &Fq2faArgs) -> Result<()> {
+    let mut reader = compression::open(&args.input).map(fastq::Reader::new)?;
+    let mut output = compression::create(args.output.as_ref())?;
+
+    let line_width = match args.line_width {
+        0 => usize::MAX,
+        n => n,
+    };
+    let mut writer = fasta::writer::Builder::default()
+        .set_line_base_count(line_width)
+        .build_with_writer(&mut output);
+
+    for result in reader.records() {
         let record = result?;
-        let name = String::from_utf8(record.name().to_vec())?;
+        let name = record.name().to_vec();
+        let description = Some(record.description())
+            .filter(|description| !description.is_empty())
+            .map(|description| description.to_vec());
         let sequence = fasta::record::Sequence::from(record.sequence().to_vec());
-        let fasta_record = fasta::Record::new(fasta::record::Definition::new(name, None), sequence);
+        let fasta_record =
+            fasta::Record::new(fasta::record::Definition::new(name, description), sequence);
         writer.write_record(&fasta_record)?;
     }
 
+    output.finish()?;
+
     Ok(())
 }
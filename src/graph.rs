@@ -8,6 +8,8 @@ use std::path::PathBuf;
 
 mod analysis;
 mod data;
+// graph edit distance is not used by a command yet, only by its tests
+#[allow(dead_code)]
 mod ged;
 mod load;
 // mod vis;
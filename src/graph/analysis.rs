@@ -15,9 +15,13 @@ pub trait GraphAnalysis {
     fn is_weakly_connected(&self) -> bool;
 
     fn density(&self) -> f64;
+    // not used by the graph command yet
+    #[allow(dead_code)]
     fn node_count(&self) -> usize;
+    #[allow(dead_code)]
     fn edge_count(&self) -> usize;
 
+    #[allow(dead_code)]
     fn walk(&self);
 
     // centrality measures
@@ -37,6 +41,7 @@ pub trait GraphAnalysis {
 
     fn is_cyclic_directed(&self) -> bool;
 
+    #[allow(dead_code)]
     fn vis(&self);
 
     fn to_cyjson(&self) -> String;
@@ -245,7 +250,7 @@ impl GraphAnalysis for NLGraph {
                 // remove first and second element of edge_props
                 let data = edge_props.as_array();
 
-                let edge_data = data.iter().last().unwrap().iter().last().unwrap();
+                let edge_data = data.iter().next_back().unwrap().iter().next_back().unwrap();
                 let edge = serde_json::json!({ "data": edge_data});
                 e.push(edge);
             }
@@ -374,4 +379,12 @@ mod tests {
         let _cyjson = nlgraph().to_cyjson();
         println!("{}", _cyjson);
     }
+
+    #[test]
+    fn test_cyjson_edge_data() {
+        let cyjson: serde_json::Value = serde_json::from_str(&load_nlgraph().to_cyjson()).unwrap();
+        let edges = cyjson["elements"]["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["data"]["label"], "TRA_(False, MicroHomology(G))_1");
+    }
 }
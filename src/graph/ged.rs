@@ -14,14 +14,13 @@ const COST_SUB: usize = 1;
 const COST_ADD_DEL: usize = 1;
 
 fn ged_recursive(
-    graph1: &DiGraph<(), ()>,
     graph2: &DiGraph<(), ()>,
     mapping: &mut [(Option<NodeIndex>, Option<NodeIndex>)],
     index: usize,
 ) -> usize {
     if index == mapping.len() {
         // All nodes are processed, calculate the edit distance for this mapping
-        return calculate_edit_distance(graph1, graph2, mapping);
+        return calculate_edit_distance(graph2, mapping);
     }
 
     let mut min_distance = usize::MAX;
@@ -31,13 +30,13 @@ fn ged_recursive(
         let node2 = NodeIndex::new(i);
         if !mapping.iter().any(|(_, b)| *b == Some(node2)) {
             mapping[index] = (Some(NodeIndex::new(index)), Some(node2));
-            min_distance = min_distance.min(ged_recursive(graph1, graph2, mapping, index + 1));
+            min_distance = min_distance.min(ged_recursive(graph2, mapping, index + 1));
         }
     }
 
     // Option 2: Delete the current node in graph1 (map it to None in graph2)
     mapping[index] = (Some(NodeIndex::new(index)), None);
-    min_distance = min_distance.min(ged_recursive(graph1, graph2, mapping, index + 1));
+    min_distance = min_distance.min(ged_recursive(graph2, mapping, index + 1));
 
     // Reset the mapping for the next iteration
     mapping[index] = (None, None);
@@ -45,13 +44,11 @@ fn ged_recursive(
 }
 
 fn calculate_edit_distance(
-    graph1: &DiGraph<(), ()>,
     graph2: &DiGraph<(), ()>,
     mapping: &[(Option<NodeIndex>, Option<NodeIndex>)],
 ) -> usize {
     let mut edit_distance = 0;
 
-    let mapped_nodes1: HashSet<_> = mapping.iter().filter_map(|(a, _)| *a).collect();
     let mapped_nodes2: HashSet<_> = mapping.iter().filter_map(|(_, b)| *b).collect();
 
     // Cost of node substitutions and deletions
@@ -72,7 +69,7 @@ fn calculate_edit_distance(
 fn graph_edit_distance(graph1: &DiGraph<(), ()>, graph2: &DiGraph<(), ()>) -> usize {
     let node_count = graph1.node_count().max(graph2.node_count());
     let mut mapping = vec![(None, None); node_count];
-    ged_recursive(graph1, graph2, &mut mapping, 0)
+    ged_recursive(graph2, &mut mapping, 0)
 }
 
 #[cfg(test)]
@@ -102,7 +99,19 @@ mod tests {
         // Example: Add nodes to graph1 and graph2
         // ...
 
-        let distance = graph_edit_distance(&graph1, &graph2);
-        println!("Graph Edit Distance: {}", distance);
+        // every node of graph1 is substituted by one of graph2
+        assert_eq!(graph_edit_distance(&graph1, &graph2), 4);
+    }
+
+    #[test]
+    fn test_ged_deletions() {
+        let mut graph1 = DiGraph::new();
+        let graph2 = DiGraph::new();
+
+        let gn1 = graph1.add_node(());
+        let gn2 = graph1.add_node(());
+        graph1.add_edge(gn1, gn2, ());
+
+        assert_eq!(graph_edit_distance(&graph1, &graph2), 2);
     }
 }
@@ -1,76 +1,293 @@
-use std::io;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
-use noodles_core::Position;
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum, ValueHint};
+use log::info;
+use noodles_csi::binning_index::{self, BinningIndex};
 
-use noodles_bam::{self as bam, bai};
-use noodles_csi::binning_index::{index::reference_sequence::bin::Chunk, Indexer};
-use noodles_sam::{self as sam};
-use sam::alignment::RecordBuf;
+use crate::compression::Compression;
+use crate::sort;
 
-fn is_coordinate_sorted(header: &sam::Header) -> bool {
-    use sam::header::record::value::map::header::{sort_order, tag};
+mod bam;
+mod fai;
+mod tabix;
 
-    header
-        .header()
-        .and_then(|hdr| hdr.other_fields().get(&tag::SORT_ORDER))
-        .map(|sort_order| sort_order == sort_order::COORDINATE)
-        .unwrap_or_default()
+/// BAI and TBI bins are fixed to a minimum interval of 2^14 bp and 5 levels.
+const LINEAR_MIN_SHIFT: u8 = 14;
+const LINEAR_DEPTH: u8 = 5;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    Bam,
+    Sam,
+    Vcf,
+    Bed,
+    Fasta,
+    Fastq,
+}
+
+impl InputFormat {
+    fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let path = match path.extension()?.to_str()? {
+            "gz" | "bgz" => Path::new(path.file_stem()?),
+            _ => path,
+        };
+
+        match path.extension()?.to_str()? {
+            "bam" => Some(Self::Bam),
+            "sam" => Some(Self::Sam),
+            "vcf" => Some(Self::Vcf),
+            "bed" => Some(Self::Bed),
+            "fa" | "fasta" | "fna" | "fas" => Some(Self::Fasta),
+            "fq" | "fastq" => Some(Self::Fastq),
+            _ => None,
+        }
+    }
 }
 
-fn alignment_context(
-    record: &sam::alignment::RecordBuf,
-) -> io::Result<(Option<usize>, Option<Position>, Option<Position>)> {
-    Ok((
-        record.reference_sequence_id(),
-        record.alignment_start(),
-        record.alignment_end(),
-    ))
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    /// Input file: BAM, bgzipped SAM/VCF/BED, or plain or bgzipped FASTA/FASTQ
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Input format. Default is inferred from the file extension
+    #[arg(long = "format", value_enum)]
+    format: Option<InputFormat>,
+
+    /// Output index file, `-` for stdout. Default is the input path with
+    /// .bai, .tbi, .csi or .fai appended
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Write a CSI index instead of BAI or TBI, needed for references longer than 2^29 bp
+    #[arg(long = "csi", default_value = "false")]
+    csi: bool,
+
+    /// Minimum interval size of the CSI bins, as a power of two
+    #[arg(
+        short = 'm',
+        long = "min-shift",
+        default_value = "14",
+        requires = "csi"
+    )]
+    min_shift: u8,
+
+    /// Number of levels of the CSI binning scheme
+    #[arg(long = "depth", default_value = "5", requires = "csi")]
+    depth: u8,
+
+    /// Sort an unsorted BAM by coordinate into a .sorted.bam next to it first (reads.bam into
+    /// reads.sorted.bam), and index that
+    #[arg(long = "sort", default_value = "false")]
+    sort: bool,
+
+    /// Number of threads to decompress the input
+    #[arg(short = 't', long = "threads", default_value = "1")]
+    threads: NonZeroUsize,
 }
 
-pub fn index_bam<P: AsRef<Path>, W: io::Write>(file: P, index_file: Option<W>) -> io::Result<()> {
-    let mut reader = bam::io::reader::Builder.build_from_path(file.as_ref())?;
-    let header = reader.read_header()?;
+impl IndexArgs {
+    fn format(&self) -> Result<InputFormat> {
+        self.format
+            .or_else(|| InputFormat::from_path(&self.input))
+            .with_context(|| {
+                format!(
+                    "cannot infer the format of {}; use --format",
+                    self.input.display()
+                )
+            })
+    }
+
+    fn binning(&self) -> Result<Binning> {
+        if !self.csi {
+            return Ok(Binning::Linear);
+        }
+
+        if self.min_shift == 0 || self.depth == 0 {
+            bail!("--min-shift and --depth must be greater than 0");
+        }
 
-    if !is_coordinate_sorted(&header) {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "the input BAM must be coordinate-sorted to be indexed",
-        ));
+        Ok(Binning::Csi {
+            min_shift: self.min_shift,
+            depth: self.depth,
+        })
     }
 
-    let mut record = RecordBuf::default();
+    fn output(&self, src: &Path, format: InputFormat) -> PathBuf {
+        self.output.clone().unwrap_or_else(|| {
+            let ext = match (format, self.csi) {
+                (InputFormat::Fasta | InputFormat::Fastq, _) => "fai",
+                (_, true) => "csi",
+                (InputFormat::Bam, false) => "bai",
+                (_, false) => "tbi",
+            };
+            with_suffix(src, ext)
+        })
+    }
+}
 
-    let mut builder = Indexer::default();
-    let mut start_position = reader.virtual_position();
+/// The binning scheme of the index: BAI/TBI with a linear index, or CSI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binning {
+    Linear,
+    Csi { min_shift: u8, depth: u8 },
+}
 
-    while reader.read_record_buf(&header, &mut record)? != 0 {
-        let end_position = reader.virtual_position();
-        let chunk = Chunk::new(start_position, end_position);
+impl Binning {
+    fn min_shift_and_depth(self) -> (u8, u8) {
+        match self {
+            Self::Linear => (LINEAR_MIN_SHIFT, LINEAR_DEPTH),
+            Self::Csi { min_shift, depth } => (min_shift, depth),
+        }
+    }
 
-        let alignment_context = match alignment_context(&record)? {
-            (Some(id), Some(start), Some(end)) => {
-                let is_mapped = !record.flags().is_unmapped();
-                Some((id, start, end, is_mapped))
-            }
-            _ => None,
-        };
+    /// The largest reference length the binning scheme can address.
+    fn max_reference_length(self) -> u64 {
+        let (min_shift, depth) = self.min_shift_and_depth();
+        1u64.checked_shl(u32::from(min_shift) + 3 * u32::from(depth))
+            .unwrap_or(u64::MAX)
+    }
+
+    fn check_reference_length(self, name: &str, len: u64) -> io::Result<()> {
+        let max_len = self.max_reference_length();
+
+        if len > max_len {
+            let hint = match self {
+                Self::Linear => "use --csi",
+                Self::Csi { .. } => "increase --min-shift or --depth",
+            };
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("reference {name} ({len} bp) is longer than {max_len} bp; {hint}"),
+            ));
+        }
+
+        Ok(())
+    }
 
-        builder.add_record(alignment_context, chunk)?;
+    /// Applies the CSI binning parameters, which `Indexer::build` does not carry over.
+    fn apply<I>(self, index: binning_index::Index<I>) -> binning_index::Index<I>
+    where
+        I: binning_index::index::reference_sequence::Index + Clone,
+    {
+        let (min_shift, depth) = self.min_shift_and_depth();
+
+        let mut builder = binning_index::Index::builder()
+            .set_min_shift(min_shift)
+            .set_depth(depth)
+            .set_reference_sequences(index.reference_sequences().to_vec());
+        if let Some(header) = index.header() {
+            builder = builder.set_header(header.clone());
+        }
+        if let Some(count) = index.unplaced_unmapped_record_count() {
+            builder = builder.set_unplaced_unmapped_record_count(count);
+        }
+
+        builder.build()
+    }
+}
+
+fn with_suffix<P: AsRef<Path>>(src: P, ext: &str) -> PathBuf {
+    let mut dst = src.as_ref().as_os_str().to_owned();
+    dst.push(".");
+    dst.push(ext);
+    dst.into()
+}
 
-        start_position = end_position;
+/// Writes to `dst`, `-` for stdout, and does not leave a truncated file behind on failure.
+fn write_to<F>(dst: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn io::Write) -> io::Result<()>,
+{
+    if dst.as_os_str() == "-" {
+        let mut stdout = io::stdout().lock();
+        return f(&mut stdout).map_err(Into::into);
     }
 
-    let index = builder.build(header.reference_sequences().len());
+    let mut writer = File::create(dst)
+        .map(BufWriter::new)
+        .with_context(|| format!("failed to create {}", dst.display()))?;
 
-    if let Some(index_file) = index_file {
-        let mut writer = bai::Writer::new(index_file);
-        writer.write_index(&index)?;
-    } else {
-        let stdout = io::stdout().lock();
-        let mut writer = bai::Writer::new(stdout);
-        writer.write_index(&index)?;
+    if let Err(e) = f(&mut writer).and_then(|_| io::Write::flush(&mut writer)) {
+        drop(writer);
+        let _ = fs::remove_file(dst);
+        return Err(e.into());
     }
 
+    info!("wrote index to {}", dst.display());
     Ok(())
 }
+
+pub fn index(args: &IndexArgs) -> Result<()> {
+    let format = args.format()?;
+    let binning = args.binning()?;
+    let mut src = args.input.clone();
+
+    if args.sort {
+        if format != InputFormat::Bam {
+            bail!("--sort only applies to BAM files");
+        }
+
+        let is_sorted = sort::is_coordinate_sorted(&src, args.threads)
+            .with_context(|| format!("failed to read {}", src.display()))?;
+        if !is_sorted {
+            let sorted = src.with_extension("sorted.bam");
+            info!("sorting {} into {}", src.display(), sorted.display());
+            sort::Sorter::coordinate(args.threads).sort_bam(&src, Some(&sorted))?;
+            src = sorted;
+        }
+    }
+
+    let src = &src;
+    let dst = args.output(src, format);
+
+    let compression =
+        Compression::from_path(src).with_context(|| format!("failed to open {}", src.display()))?;
+
+    let result = match format {
+        InputFormat::Bam => write_to(&dst, |writer| {
+            bam::index_bam(src, writer, binning, args.threads)
+        }),
+        InputFormat::Sam | InputFormat::Vcf | InputFormat::Bed => {
+            if compression != Compression::Bgzf {
+                bail!(
+                    "{} must be compressed with bgzip to be indexed",
+                    src.display()
+                );
+            }
+            write_to(&dst, |writer| {
+                tabix::index_text(src, format, writer, binning, args.threads)
+            })
+        }
+        InputFormat::Fasta | InputFormat::Fastq => {
+            if args.csi {
+                bail!("--csi only applies to BAM, SAM, VCF and BED files");
+            }
+            match compression {
+                Compression::None => {}
+                Compression::Gzip | Compression::Zstd => {
+                    bail!(
+                        "{} must be compressed with bgzip to be indexed",
+                        src.display()
+                    )
+                }
+                Compression::Bgzf => {
+                    let gzi = with_suffix(src, "gzi");
+                    write_to(&gzi, |writer| fai::index_gzi(src, writer))
+                        .with_context(|| format!("failed to index {}", src.display()))?;
+                }
+            }
+            write_to(&dst, |writer| {
+                fai::index_fai(src, format, compression, writer, args.threads)
+            })
+        }
+    };
+
+    result.with_context(|| format!("failed to index {}", src.display()))
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use log::warn;
+use noodles_core::Position;
+
+use noodles_bam::{self as bam, bai};
+use noodles_bgzf as bgzf;
+use noodles_csi::{
+    self as csi,
+    binning_index::{
+        index::reference_sequence::{bin::Chunk, index::BinnedIndex},
+        Index, Indexer,
+    },
+};
+use noodles_sam::{self as sam};
+use sam::alignment::RecordBuf;
+
+use super::Binning;
+
+fn is_coordinate_sorted(header: &sam::Header) -> bool {
+    use sam::header::record::value::map::header::{sort_order, tag};
+
+    header
+        .header()
+        .and_then(|hdr| hdr.other_fields().get(&tag::SORT_ORDER))
+        .map(|sort_order| sort_order == sort_order::COORDINATE)
+        .unwrap_or_default()
+}
+
+fn alignment_context(
+    record: &sam::alignment::RecordBuf,
+) -> io::Result<(Option<usize>, Option<Position>, Option<Position>)> {
+    Ok((
+        record.reference_sequence_id(),
+        record.alignment_start(),
+        record.alignment_end(),
+    ))
+}
+
+fn build_index<R, I>(
+    reader: &mut bam::io::Reader<bgzf::Reader<R>>,
+    header: &sam::Header,
+    mut indexer: Indexer<I>,
+) -> io::Result<Index<I>>
+where
+    R: io::Read,
+    I: csi::binning_index::index::reference_sequence::Index + Default,
+{
+    let mut record = RecordBuf::default();
+    let mut start_position = reader.virtual_position();
+    let mut last = (0, 0);
+    let mut n = 0;
+
+    while reader.read_record_buf(header, &mut record)? != 0 {
+        let end_position = reader.virtual_position();
+        let chunk = Chunk::new(start_position, end_position);
+        n += 1;
+
+        // the header is not trusted, unplaced reads must come last
+        let current = (
+            record.reference_sequence_id().unwrap_or(usize::MAX),
+            record
+                .alignment_start()
+                .map(usize::from)
+                .unwrap_or_default(),
+        );
+        if current < last {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the input BAM is not coordinate-sorted (record {n}); \
+                     sort it with `rboss sort` or use `rboss index --sort`"
+                ),
+            ));
+        }
+        last = current;
+
+        let alignment_context = match alignment_context(&record)? {
+            (Some(id), Some(start), Some(end)) => {
+                let is_mapped = !record.flags().is_unmapped();
+                Some((id, start, end, is_mapped))
+            }
+            _ => None,
+        };
+
+        indexer.add_record(alignment_context, chunk)?;
+
+        start_position = end_position;
+    }
+
+    Ok(indexer.build(header.reference_sequences().len()))
+}
+
+pub fn index_bam<P: AsRef<Path>, W: io::Write>(
+    file: P,
+    index_file: W,
+    binning: Binning,
+    worker_count: NonZeroUsize,
+) -> io::Result<()> {
+    let mut reader = bgzf::reader::Builder::default()
+        .set_worker_count(worker_count)
+        .build_from_path(file.as_ref())
+        .map(bam::io::Reader::from)?;
+    let header = reader.read_header()?;
+
+    if !is_coordinate_sorted(&header) {
+        warn!("the BAM header does not declare SO:coordinate, checking the record order");
+    }
+
+    for (name, reference_sequence) in header.reference_sequences() {
+        let len = usize::from(reference_sequence.length()) as u64;
+        binning.check_reference_length(&name.to_string(), len)?;
+    }
+
+    match binning {
+        Binning::Linear => {
+            let index = build_index(&mut reader, &header, Indexer::default())?;
+            bai::Writer::new(index_file).write_index(&index)
+        }
+        Binning::Csi { min_shift, depth } => {
+            let indexer = Indexer::<BinnedIndex>::new(min_shift, depth);
+            let index = build_index(&mut reader, &header, indexer)?;
+            csi::Writer::new(index_file).write_index(&binning.apply(index))
+        }
+    }
+}
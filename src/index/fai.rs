@@ -0,0 +1,297 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use noodles_bgzf as bgzf;
+use noodles_fasta as fasta;
+use noodles_fastq as fastq;
+
+use super::InputFormat;
+use crate::compression::Compression;
+
+const BGZF_HEADER_LEN: usize = 18;
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// The name of a FASTA/FASTQ record, up to the first whitespace.
+fn record_name(definition: &[u8]) -> io::Result<String> {
+    let name = trim_newline(definition)
+        .split(|b| b.is_ascii_whitespace())
+        .next()
+        .unwrap_or_default();
+
+    if name.is_empty() {
+        return Err(invalid_data("missing record name"));
+    }
+
+    String::from_utf8(name.to_vec()).map_err(invalid_data)
+}
+
+struct FastaRecord {
+    name: String,
+    length: u64,
+    offset: u64,
+    line_bases: u64,
+    line_width: u64,
+    is_last_line: bool,
+}
+
+impl FastaRecord {
+    fn add_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let bases = trim_newline(line).len() as u64;
+        let width = line.len() as u64;
+
+        if bases == 0 {
+            self.is_last_line = self.line_bases > 0;
+            return Ok(());
+        }
+
+        if self.line_bases == 0 {
+            self.line_bases = bases;
+            self.line_width = width;
+        } else if self.is_last_line
+            || bases > self.line_bases
+            || (line.ends_with(b"\n") && bases == self.line_bases && width != self.line_width)
+        {
+            return Err(invalid_data(format!(
+                "inconsistent line length in sequence {}",
+                self.name
+            )));
+        }
+
+        if bases < self.line_bases || !line.ends_with(b"\n") {
+            self.is_last_line = true;
+        }
+
+        self.length += bases;
+        Ok(())
+    }
+}
+
+impl From<FastaRecord> for fasta::fai::Record {
+    fn from(record: FastaRecord) -> Self {
+        Self::new(
+            record.name,
+            record.length,
+            record.offset,
+            record.line_bases,
+            record.line_width,
+        )
+    }
+}
+
+/// Builds a FASTA index with offsets into the uncompressed stream.
+fn index_fasta<R: BufRead>(mut reader: R) -> io::Result<fasta::fai::Index> {
+    let mut index = Vec::new();
+    let mut record: Option<FastaRecord> = None;
+    let mut offset = 0;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)? as u64;
+        if n == 0 {
+            break;
+        }
+        offset += n;
+
+        if let Some(definition) = line.strip_prefix(b">") {
+            index.extend(record.take().map(fasta::fai::Record::from));
+            record = Some(FastaRecord {
+                name: record_name(definition)?,
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+                is_last_line: false,
+            });
+        } else if let Some(record) = record.as_mut() {
+            record.add_line(&line)?;
+        } else if !trim_newline(&line).is_empty() {
+            return Err(invalid_data("expected a FASTA definition line ('>')"));
+        }
+    }
+
+    index.extend(record.map(fasta::fai::Record::from));
+    Ok(index)
+}
+
+/// Builds a FASTQ index. Sequences and qualities must be on a single line.
+fn index_fastq<R: BufRead>(mut reader: R) -> io::Result<fastq::fai::Index> {
+    let mut index = Vec::new();
+    let mut offset = 0;
+    let mut line = Vec::new();
+
+    let mut read_line = |line: &mut Vec<u8>, offset: &mut u64| -> io::Result<u64> {
+        line.clear();
+        let n = reader.read_until(b'\n', line)? as u64;
+        *offset += n;
+        Ok(n)
+    };
+
+    loop {
+        if read_line(&mut line, &mut offset)? == 0 {
+            break;
+        }
+        if trim_newline(&line).is_empty() {
+            continue;
+        }
+
+        let name = line
+            .strip_prefix(b"@")
+            .ok_or_else(|| invalid_data("expected a FASTQ name line ('@')"))
+            .and_then(record_name)?;
+
+        let sequence_offset = offset;
+        read_line(&mut line, &mut offset)?;
+        let line_bases = trim_newline(&line).len() as u64;
+        let line_width = line.len() as u64;
+
+        read_line(&mut line, &mut offset)?;
+        if !line.starts_with(b"+") {
+            return Err(invalid_data(format!(
+                "expected '+' after the sequence of {name}; multi-line FASTQ is not supported"
+            )));
+        }
+
+        let quality_scores_offset = offset;
+        read_line(&mut line, &mut offset)?;
+        if trim_newline(&line).len() as u64 != line_bases {
+            return Err(invalid_data(format!(
+                "sequence and quality lengths differ in {name}"
+            )));
+        }
+
+        index.push(fastq::fai::Record::new(
+            name,
+            line_bases,
+            sequence_offset,
+            line_bases,
+            line_width,
+            quality_scores_offset,
+        ));
+    }
+
+    Ok(index)
+}
+
+pub fn index_fai<P: AsRef<Path>>(
+    file: P,
+    format: InputFormat,
+    compression: Compression,
+    index_file: &mut dyn io::Write,
+    worker_count: NonZeroUsize,
+) -> io::Result<()> {
+    let reader: Box<dyn BufRead> = match compression {
+        Compression::Bgzf => Box::new(
+            bgzf::reader::Builder::default()
+                .set_worker_count(worker_count)
+                .build_from_path(file.as_ref())?,
+        ),
+        _ => Box::new(File::open(file.as_ref()).map(BufReader::new)?),
+    };
+
+    match format {
+        InputFormat::Fasta => {
+            let index = index_fasta(reader)?;
+            fasta::fai::Writer::new(index_file).write_index(&index)
+        }
+        InputFormat::Fastq => {
+            let mut writer = fastq::fai::Writer::new(index_file);
+            for record in index_fastq(reader)? {
+                writer.write_record(&record)?;
+            }
+            Ok(())
+        }
+        _ => unreachable!("{format:?} is not indexed with a FAI"),
+    }
+}
+
+/// Writes the GZI index of a BGZF file: the compressed and uncompressed
+/// offsets of every block but the first.
+pub fn index_gzi<P: AsRef<Path>>(file: P, index_file: &mut dyn io::Write) -> io::Result<()> {
+    let mut reader = File::open(file.as_ref()).map(BufReader::new)?;
+    let mut entries = Vec::new();
+    let (mut compressed_offset, mut uncompressed_offset) = (0u64, 0u64);
+
+    let mut header = [0; BGZF_HEADER_LEN];
+    let mut block = Vec::new();
+
+    while !reader.fill_buf()?.is_empty() {
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != [0x1f, 0x8b, 0x08, 0x04] || header[10..14] != [6, 0, b'B', b'C'] {
+            return Err(invalid_data("invalid BGZF block header"));
+        }
+
+        let block_size = usize::from(u16::from_le_bytes([header[16], header[17]])) + 1;
+        block.resize(block_size - BGZF_HEADER_LEN, 0);
+        reader.read_exact(&mut block)?;
+
+        let isize_bytes = block
+            .get(block.len().saturating_sub(4)..)
+            .filter(|bytes| bytes.len() == 4)
+            .ok_or_else(|| invalid_data("truncated BGZF block"))?;
+        let block_len = u32::from_le_bytes(isize_bytes.try_into().unwrap());
+
+        if compressed_offset > 0 && block_len > 0 {
+            entries.push((compressed_offset, uncompressed_offset));
+        }
+
+        compressed_offset += block_size as u64;
+        uncompressed_offset += u64::from(block_len);
+    }
+
+    index_file.write_all(&(entries.len() as u64).to_le_bytes())?;
+    for (compressed_offset, uncompressed_offset) in entries {
+        index_file.write_all(&compressed_offset.to_le_bytes())?;
+        index_file.write_all(&uncompressed_offset.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_fasta() {
+        let data = b">sq0 description\nACGT\nACGT\nAC\n>sq1\r\nAAA\r\nA\r\n>sq2\n";
+        let index = index_fasta(&data[..]).unwrap();
+
+        assert_eq!(
+            index,
+            vec![
+                fasta::fai::Record::new("sq0", 10, 17, 4, 5),
+                fasta::fai::Record::new("sq1", 4, 36, 3, 5),
+                fasta::fai::Record::new("sq2", 0, 49, 0, 0),
+            ]
+        );
+
+        let data = b">sq0\nACGT\nAC\nACGT\n";
+        assert!(index_fasta(&data[..]).is_err());
+    }
+
+    #[test]
+    fn test_index_fastq() {
+        let data = b"@r0 comment\nACGT\n+\nNDLS\n@r1\nAC\n+r1\nDL\n";
+        let index = index_fastq(&data[..]).unwrap();
+
+        assert_eq!(
+            index,
+            vec![
+                fastq::fai::Record::new("r0", 4, 12, 4, 5, 19),
+                fastq::fai::Record::new("r1", 2, 28, 2, 3, 35),
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use std::io::{self, BufRead};
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use noodles_bgzf as bgzf;
+use noodles_core::Position;
+use noodles_csi::{
+    self as csi,
+    binning_index::{
+        index::{
+            header::{self, ReferenceSequenceNames},
+            reference_sequence::{
+                bin::Chunk,
+                index::{BinnedIndex, LinearIndex},
+            },
+        },
+        Index, Indexer,
+    },
+};
+use noodles_tabix as tabix;
+
+use super::{Binning, InputFormat};
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn parse_position(s: &str) -> io::Result<usize> {
+    s.parse()
+        .map_err(|_| invalid_data(format!("invalid position: {s}")))
+}
+
+fn to_position(n: usize) -> io::Result<Position> {
+    Position::new(n).ok_or_else(|| invalid_data("position must be greater than 0"))
+}
+
+/// The length of the reference covered by a SAM CIGAR string.
+fn cigar_reference_len(cigar: &str) -> io::Result<usize> {
+    let mut len = 0;
+    let mut n = 0usize;
+
+    for c in cigar.bytes() {
+        match c {
+            b'0'..=b'9' => n = n * 10 + usize::from(c - b'0'),
+            b'M' | b'D' | b'N' | b'=' | b'X' => {
+                len += n;
+                n = 0;
+            }
+            b'I' | b'S' | b'H' | b'P' => n = 0,
+            _ => return Err(invalid_data(format!("invalid CIGAR: {cigar}"))),
+        }
+    }
+
+    Ok(len)
+}
+
+/// Parses the reference name and the 1-based closed interval of a line,
+/// or `None` for header, comment and unplaced lines.
+fn parse_interval(
+    format: InputFormat,
+    line: &str,
+) -> io::Result<Option<(&str, Position, Position)>> {
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    let fields: Vec<_> = line.split('\t').collect();
+    let field = |i: usize| {
+        fields
+            .get(i)
+            .copied()
+            .ok_or_else(|| invalid_data(format!("missing column {}: {line}", i + 1)))
+    };
+
+    let (name, start, end) = match format {
+        InputFormat::Vcf => {
+            if line.starts_with('#') {
+                return Ok(None);
+            }
+
+            let start = parse_position(field(1)?)?;
+            let reference_bases = field(3)?;
+            let end = field(7)?
+                .split(';')
+                .find_map(|info| info.strip_prefix("END="))
+                .map(parse_position)
+                .transpose()?
+                .unwrap_or(start + reference_bases.len().max(1) - 1);
+
+            (field(0)?, start, end.max(start))
+        }
+        InputFormat::Bed => {
+            if line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+                return Ok(None);
+            }
+
+            // BED is 0-based and half-open
+            let start = parse_position(field(1)?)? + 1;
+            let end = parse_position(field(2)?)?;
+
+            (field(0)?, start, end.max(start))
+        }
+        InputFormat::Sam => {
+            if line.starts_with('@') {
+                return Ok(None);
+            }
+
+            let name = field(2)?;
+            let start = parse_position(field(3)?)?;
+            if name == "*" || start == 0 {
+                return Ok(None);
+            }
+
+            let len = match field(5)? {
+                "*" => 0,
+                cigar => cigar_reference_len(cigar)?,
+            };
+
+            (name, start, start + len.max(1) - 1)
+        }
+        _ => unreachable!("{format:?} is not a tab-delimited format"),
+    };
+
+    Ok(Some((name, to_position(start)?, to_position(end)?)))
+}
+
+fn build_index<R, I>(
+    reader: &mut bgzf::Reader<R>,
+    format: InputFormat,
+    binning: Binning,
+    indexer: Indexer<I>,
+) -> io::Result<Index<I>>
+where
+    R: io::Read,
+    I: csi::binning_index::index::reference_sequence::Index + Default,
+{
+    let mut indexer = indexer;
+    let mut reference_sequence_names = ReferenceSequenceNames::new();
+    let mut last: Option<(usize, Position)> = None;
+
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut start_position = reader.virtual_position();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let end_position = reader.virtual_position();
+        let chunk = Chunk::new(start_position, end_position);
+        start_position = end_position;
+
+        let Some((name, start, end)) = parse_interval(format, line.trim_end_matches(['\n', '\r']))?
+        else {
+            continue;
+        };
+
+        let (id, is_new) = reference_sequence_names.insert_full(name.into());
+
+        match last {
+            Some((last_id, _)) if id != last_id && !is_new => {
+                return Err(invalid_data(format!(
+                    "the input is not sorted: {name} is not contiguous (line {line_number})"
+                )));
+            }
+            Some((last_id, last_start)) if id == last_id && start < last_start => {
+                return Err(invalid_data(format!(
+                    "the input is not sorted: {name}:{start} comes after {name}:{last_start} (line {line_number})"
+                )));
+            }
+            _ => {}
+        }
+
+        binning.check_reference_length(name, usize::from(end) as u64)?;
+        indexer.add_record(Some((id, start, end, true)), chunk)?;
+        last = Some((id, start));
+    }
+
+    let builder = match format {
+        InputFormat::Vcf => header::Builder::vcf(),
+        InputFormat::Bed => header::Builder::bed(),
+        _ => header::Builder::sam(),
+    };
+
+    let reference_sequence_count = reference_sequence_names.len();
+    let header = builder
+        .set_reference_sequence_names(reference_sequence_names)
+        .build();
+
+    Ok(indexer.set_header(header).build(reference_sequence_count))
+}
+
+pub fn index_text<P: AsRef<Path>, W: io::Write>(
+    file: P,
+    format: InputFormat,
+    index_file: W,
+    binning: Binning,
+    worker_count: NonZeroUsize,
+) -> io::Result<()> {
+    let mut reader = bgzf::reader::Builder::default()
+        .set_worker_count(worker_count)
+        .build_from_path(file.as_ref())?;
+
+    match binning {
+        Binning::Linear => {
+            let indexer = Indexer::<LinearIndex>::default();
+            let index = build_index(&mut reader, format, binning, indexer)?;
+            tabix::Writer::new(index_file).write_index(&index)
+        }
+        Binning::Csi { min_shift, depth } => {
+            let indexer = Indexer::<BinnedIndex>::new(min_shift, depth);
+            let index = build_index(&mut reader, format, binning, indexer)?;
+            csi::Writer::new(index_file).write_index(&binning.apply(index))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(format: InputFormat, line: &str) -> Option<(String, usize, usize)> {
+        parse_interval(format, line)
+            .unwrap()
+            .map(|(name, start, end)| (name.to_string(), usize::from(start), usize::from(end)))
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(interval(InputFormat::Vcf, "##fileformat=VCFv4.3"), None);
+        assert_eq!(
+            interval(InputFormat::Vcf, "chr1\t100\t.\tACG\tA\t.\t.\tDP=3"),
+            Some(("chr1".into(), 100, 102))
+        );
+        assert_eq!(
+            interval(
+                InputFormat::Vcf,
+                "chr1\t100\t.\tN\t<DEL>\t.\t.\tSVTYPE=DEL;END=500"
+            ),
+            Some(("chr1".into(), 100, 500))
+        );
+
+        assert_eq!(interval(InputFormat::Bed, "track name=x"), None);
+        assert_eq!(
+            interval(InputFormat::Bed, "chr2\t0\t10\tname"),
+            Some(("chr2".into(), 1, 10))
+        );
+
+        assert_eq!(interval(InputFormat::Sam, "@HD\tVN:1.6"), None);
+        assert_eq!(
+            interval(
+                InputFormat::Sam,
+                "r1\t0\tchr3\t10\t60\t2S5M2D3M1I\t*\t0\t0\t*\t*"
+            ),
+            Some(("chr3".into(), 10, 19))
+        );
+        assert_eq!(
+            interval(InputFormat::Sam, "r2\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*"),
+            None
+        );
+    }
+}
@@ -1,18 +1,27 @@
-use clap::{Command, CommandFactory, Parser, Subcommand, ValueHint};
+use clap::{error::ErrorKind, Command, CommandFactory, Parser, Subcommand};
 use env_logger::Builder;
 use human_panic::setup_panic;
 use log::info;
 use log::LevelFilter;
-use std::path::PathBuf;
 
 use clap_complete::{generate, Generator, Shell};
 use std::io;
 
+mod bam2fq;
+mod compression;
+mod dedup;
 mod extract;
 mod fa2fq;
+mod filter;
+mod fq2bam;
 mod fq2fa;
 mod index;
 mod rsoft;
+mod sample;
+mod seq;
+mod seqstats;
+mod sort;
+mod split;
 
 // mod anno;
 mod graph;
@@ -36,58 +45,47 @@ struct Cli {
 enum Commands {
     /// Extract reads from a BAM file
     #[command(visible_alias = "e")]
-    Extract {
-        /// Read IDs
-        #[arg(value_hint = ValueHint::FilePath)]
-        readids: String,
-
-        /// Bam input file
-        #[arg(value_hint = ValueHint::FilePath)]
-        input: PathBuf,
-
-        /// Is the output file a BAM file
-        #[arg(short = 'b', default_value = "false")]
-        isbam: bool,
-    },
-
-    /// Index a BAM file
-    Index {
-        /// Bam input file
-        #[arg(value_hint = ValueHint::FilePath)]
-        input: PathBuf,
-    },
+    Extract(extract::ExtractArgs),
+
+    /// Index a BAM, bgzipped SAM/VCF/BED, FASTA or FASTQ file
+    Index(index::IndexArgs),
+
+    /// Sort a BAM file by coordinate, query name or data field
+    Sort(sort::SortArgs),
+
+    /// Convert a BAM or SAM file to FASTQ
+    Bam2fq(bam2fq::Bam2fqArgs),
 
     /// Convert a FASTA file to FASTQ
-    Fa2fq {
-        /// fasta input file
-        #[arg(value_hint = ValueHint::FilePath)]
-        input: PathBuf,
-    },
+    Fa2fq(fa2fq::Fa2fqArgs),
+
+    /// Convert FASTQ files to an unaligned BAM
+    Fq2bam(fq2bam::Fq2bamArgs),
 
     /// Convert a FASTQ file to FASTA
-    Fq2fa {
-        /// fastq input file
-        #[arg(value_hint = ValueHint::FilePath)]
-        input: PathBuf,
-    },
+    Fq2fa(fq2fa::Fq2faArgs),
+
+    /// Filter FASTQ or FASTA reads by length, quality and name
+    Filter(filter::FilterArgs),
+
+    /// Remove duplicate FASTQ or FASTA reads by name or sequence
+    Dedup(dedup::DedupArgs),
+
+    /// Randomly sample reads from a FASTQ, FASTA or BAM file
+    Sample(sample::SampleArgs),
+
+    /// Reverse complement, mask or translate reads, or extract regions of a FASTA file
+    Seq(seq::SeqArgs),
+
+    /// Split a FASTQ or FASTA file into parts
+    Split(split::SplitArgs),
+
+    /// Report statistics of FASTA, FASTQ or BAM files
+    #[command(visible_alias = "stats")]
+    Seqstats(seqstats::SeqstatsArgs),
 
     /// Create soft links to files with suffix recursively
-    Rsoft {
-        /// The directory to search
-        #[arg(value_hint = ValueHint::FilePath)]
-        source: PathBuf,
-
-        /// The directory to create the softlinks. default is current directory
-        #[arg(short = 't', value_hint = ValueHint::FilePath)]
-        target: Option<PathBuf>,
-        /// The suffix of the files to link. default is all files
-        #[arg(short = 's', value_delimiter = ' ', num_args=1..)]
-        suffix: Option<Vec<String>>,
-
-        /// Overwrite existing files
-        #[arg(short = 'o', default_value = "false")]
-        overwrite: bool,
-    },
+    Rsoft(rsoft::RsoftArgs),
 
     /// Graph Analysis
     Graph(graph::GraphArgs),
@@ -99,10 +97,26 @@ fn print_completions<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
 
-fn main() {
+// human-panic 1.x still refers to `PanicInfo`, renamed after our minimum Rust version
+#[allow(deprecated)]
+fn setup_panic_hook() {
     setup_panic!();
+}
+
+fn main() {
+    setup_panic_hook();
 
     let cli = Cli::parse();
+    if let Some(Commands::Rsoft(args)) = &cli.command {
+        if let Err(message) = args.validate() {
+            let mut cmd = Cli::command();
+            cmd.build();
+            cmd.find_subcommand_mut("rsoft")
+                .unwrap()
+                .error(ErrorKind::ArgumentConflict, message)
+                .exit();
+        }
+    }
 
     let mut log_builder = Builder::from_default_env();
 
@@ -128,38 +142,116 @@ fn main() {
     // You can check for the existence of subcommands, and if found use their
     // matches just as you would the top level cmd
     match &cli.command {
-        Some(Commands::Extract {
-            readids,
-            input,
-            isbam,
-        }) => {
-            info!("'extract'  {readids:?} from {input:?} ");
-            extract::extract(readids, input, *isbam).unwrap();
+        Some(Commands::Extract(args)) => {
+            info!("'extract'  {args:?} ");
+            if let Err(e) = extract::extract(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Index(args)) => {
+            info!("'index'  {args:?} ");
+            if let Err(e) = index::index(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
         }
 
-        Some(Commands::Index { input }) => {
-            info!("'index'  {input:?} ");
-            index::index_bam(input, None::<Box<dyn io::Write>>).unwrap();
+        Some(Commands::Sort(args)) => {
+            info!("'sort'  {args:?} ");
+            if let Err(e) = sort::sort(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
         }
 
-        Some(Commands::Fa2fq { input }) => {
-            info!("'fa2fq'  {input:?} ");
-            fa2fq::fa2fq(input).unwrap();
+        Some(Commands::Bam2fq(args)) => {
+            info!("'bam2fq'  {args:?} ");
+            if let Err(e) = bam2fq::bam2fq(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
         }
 
-        Some(Commands::Fq2fa { input }) => {
-            info!("'fq2fa'  {input:?} ");
-            fq2fa::fq2fa(input).unwrap();
+        Some(Commands::Fa2fq(args)) => {
+            info!("'fa2fq'  {args:?} ");
+            if let Err(e) = fa2fq::fa2fq(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
         }
 
-        Some(Commands::Rsoft {
-            source,
-            target,
-            suffix,
-            overwrite,
-        }) => {
-            info!("'rsoft'  {target:?} {suffix:?} ");
-            rsoft::rsoft(source, target.as_ref(), suffix.clone(), *overwrite).unwrap();
+        Some(Commands::Fq2bam(args)) => {
+            info!("'fq2bam'  {args:?} ");
+            if let Err(e) = fq2bam::fq2bam(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Fq2fa(args)) => {
+            info!("'fq2fa'  {args:?} ");
+            if let Err(e) = fq2fa::fq2fa(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Filter(args)) => {
+            info!("'filter'  {args:?} ");
+            if let Err(e) = filter::filter(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Dedup(args)) => {
+            info!("'dedup'  {args:?} ");
+            if let Err(e) = dedup::dedup(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Sample(args)) => {
+            info!("'sample'  {args:?} ");
+            if let Err(e) = sample::sample(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Seq(args)) => {
+            info!("'seq'  {args:?} ");
+            if let Err(e) = seq::seq(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Split(args)) => {
+            info!("'split'  {args:?} ");
+            if let Err(e) = split::split(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Seqstats(args)) => {
+            info!("'seqstats'  {args:?} ");
+            if let Err(e) = seqstats::seqstats(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Rsoft(args)) => {
+            info!("'rsoft'  {args:?} ");
+            if let Err(e) = rsoft::rsoft(args) {
+                eprintln!("Error: {e:#}");
+                std::process::exit(1);
+            }
         }
 
         Some(Commands::Graph(args)) => {
@@ -174,7 +266,5 @@ fn main() {
 
         // // If no subcommand was used, it's a normal top level command
         None => info!("No subcommand was used"),
-
-        _ => info!("Subcommand not implemented yet!"),
     }
 }
@@ -1,74 +1,715 @@
-use anyhow::Result;
-use std::{env, path::Path};
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum, ValueHint};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::{env, io};
 
-use log::{error, info};
-use regex::Regex;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{error, info, warn};
+use regex::{Regex, RegexSet};
 use walkdir::WalkDir;
 
+mod manifest;
+mod mode;
+
+use manifest::{Entry, Manifest, ManifestFormat, Status};
+use mode::Mode;
+
 #[cfg(unix)]
 use std::os::unix::fs as unix_fs;
 
 #[cfg(windows)]
 use std::os::windows::fs as windows_fs;
 
-pub fn rsoft<P: AsRef<Path>>(
-    source_directory: P,
-    target_directory: Option<P>,
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    /// Stop without creating any link
+    Error,
+    /// Link the first file and skip the others
+    Skip,
+    /// Add the parent directory name to the link names, e.g. sample_run1.bam
+    Suffix,
+    /// Keep the source directory structure in the target directory
+    Mirror,
+}
+
+#[derive(Args, Debug)]
+pub struct RsoftArgs {
+    /// The directory to search
+    #[arg(value_hint = ValueHint::DirPath, required_unless_present = "verify")]
+    source: Option<PathBuf>,
+
+    /// The directory to create the softlinks. default is current directory
+    #[arg(short = 't', value_hint = ValueHint::DirPath)]
+    target: Option<PathBuf>,
+
+    /// The suffix of the files to link. default is all files
+    #[arg(short = 's', value_delimiter = ' ', num_args = 1..)]
     suffix: Option<Vec<String>>,
+
+    /// Glob of the files to link, relative to the source directory, e.g. '**/*.bam' or '*/pass/*'.
+    /// `*` does not match `/`, `**` does. Can be repeated
+    #[arg(short = 'g', long = "glob")]
+    globs: Vec<String>,
+
+    /// Regex of the files to link, searched in the path relative to the source directory.
+    /// Can be repeated
+    #[arg(short = 'e', long = "regex")]
+    regexes: Vec<String>,
+
+    /// Glob of the files and directories to leave out, e.g. '**/fail'. Can be repeated
+    #[arg(short = 'x', long = "exclude")]
+    excludes: Vec<String>,
+
+    /// Only link files at least this many directories below the source, 1 for its own files
+    #[arg(long = "min-depth")]
+    min_depth: Option<usize>,
+
+    /// Only link files at most this many directories below the source, 1 for its own files
+    #[arg(long = "max-depth")]
+    max_depth: Option<usize>,
+
+    /// Follow symlinked directories
+    #[arg(short = 'L', long = "follow-links")]
+    follow_links: bool,
+
+    /// Overwrite existing files
+    #[arg(short = 'o', default_value = "false")]
     overwrite: bool,
-) -> Result<()> {
-    let pattern = if suffix.is_some() {
-        let suffix_re = suffix
-            .unwrap()
-            .iter()
-            .map(|s| regex::escape(s))
-            .collect::<Vec<_>>()
-            .join("|");
-
-        format!(r".*\.({})$", suffix_re)
-    } else {
-        ".*".to_string()
+
+    /// Print the links without creating them
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Create relative symlinks, only with `--mode symlink`
+    #[arg(short = 'r', long = "relative")]
+    relative: bool,
+
+    /// How to link the files
+    #[arg(short = 'm', long = "mode", value_enum, default_value = "symlink")]
+    mode: Mode,
+
+    /// What to do when files in different directories have the same name
+    #[arg(short = 'c', long = "on-collision", value_enum, default_value = "skip")]
+    on_collision: Collision,
+
+    /// Format of the manifest of the links, kept in the target directory
+    #[arg(long = "manifest-format", value_enum, default_value = "tsv")]
+    manifest_format: ManifestFormat,
+
+    /// Record an xxh3 checksum of every linked file in the manifest, checked by --verify.
+    /// Copies are also checked right after copying
+    #[arg(long = "checksum")]
+    checksum: bool,
+
+    /// Remove links of an earlier run whose file is gone or no longer matches
+    #[arg(long = "prune")]
+    prune: bool,
+
+    /// Report links of the manifest that are missing, broken, retargeted or changed, without linking
+    #[arg(long = "verify", conflicts_with_all = ["dry_run", "prune"])]
+    verify: bool,
+}
+
+impl RsoftArgs {
+    /// Checks the combinations clap cannot tell apart, as they depend on the value of --mode.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.relative && self.mode != Mode::Symlink {
+            return Err(format!(
+                "the argument '--relative' cannot be used with '--mode {}'",
+                self.mode.as_str()
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob {pattern}"))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Decides which files are linked by their path relative to the source directory.
+/// A file has to match one of the patterns of every kind that is given.
+struct Selector {
+    suffix: Option<Regex>,
+    globs: Option<GlobSet>,
+    regexes: Option<RegexSet>,
+    excludes: GlobSet,
+}
+
+impl Selector {
+    fn new(args: &RsoftArgs) -> Result<Self> {
+        let suffix = args.suffix.as_ref().map(|suffix| {
+            let suffix_re = suffix
+                .iter()
+                .map(|s| regex::escape(s))
+                .collect::<Vec<_>>()
+                .join("|");
+
+            Regex::new(&format!(r".*\.({})$", suffix_re)).unwrap()
+        });
+
+        Ok(Self {
+            suffix,
+            globs: Some(&args.globs)
+                .filter(|globs| !globs.is_empty())
+                .map(|globs| glob_set(globs))
+                .transpose()?,
+            regexes: Some(&args.regexes)
+                .filter(|regexes| !regexes.is_empty())
+                .map(RegexSet::new)
+                .transpose()
+                .context("invalid regex")?,
+            excludes: glob_set(&args.excludes)?,
+        })
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.excludes.is_match(path)
+    }
+
+    fn is_selected(&self, path: &Path) -> bool {
+        let text = path.to_string_lossy();
+        // every kind of pattern that is not given matches, manifests of earlier runs never do
+        !manifest::is_manifest(path)
+            && self.suffix.iter().all(|re| re.is_match(&text))
+            && self.globs.iter().all(|globs| globs.is_match(path))
+            && self.regexes.iter().all(|regexes| regexes.is_match(&text))
+    }
+}
+
+/// A link to create in the target directory.
+#[derive(Debug, PartialEq, Eq)]
+struct Link {
+    source: PathBuf,
+    link: PathBuf,
+}
+
+/// The path of `path` relative to the directory `base`, both absolute.
+fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+/// The absolute path, also for a target directory that does not exist yet.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(path) => Ok(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+                Ok(absolute(parent)?.join(name))
+            }
+            _ => Ok(env::current_dir()?.join(path)),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Adds the parent directory name to a file name, before its extensions: sample.fq.gz becomes sample_run1.fq.gz.
+fn suffixed_name(path: &Path) -> OsString {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let parent = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    let (stem, extension) = match name.find('.') {
+        Some(0) | None => (&name[..], ""),
+        Some(i) => name.split_at(i),
     };
+    format!("{stem}_{parent}{extension}").into()
+}
+
+/// Decides the link of every file, resolving files with the same name.
+fn plan(
+    files: Vec<PathBuf>,
+    source_dir: &Path,
+    target_dir: &Path,
+    on_collision: Collision,
+) -> Result<Vec<Link>> {
+    if on_collision == Collision::Mirror {
+        return Ok(files
+            .into_iter()
+            .map(|source| {
+                let link = target_dir.join(source.strip_prefix(source_dir).unwrap());
+                Link { source, link }
+            })
+            .collect());
+    }
+
+    let mut sources_by_name: HashMap<OsString, Vec<&Path>> = HashMap::new();
+    for file in &files {
+        sources_by_name
+            .entry(file.file_name().unwrap().to_owned())
+            .or_default()
+            .push(file);
+    }
+
+    let mut links: Vec<Link> = Vec::new();
+    let mut linked: HashMap<PathBuf, usize> = HashMap::new();
 
-    let re = Regex::new(&pattern).unwrap();
+    for source in &files {
+        let name = source.file_name().unwrap();
+        let sources = &sources_by_name[name];
 
-    let mut target_dir = env::current_dir().unwrap();
-    if target_directory.is_some() {
-        target_dir = target_directory.unwrap().as_ref().to_path_buf();
+        let link = if sources.len() == 1 {
+            target_dir.join(name)
+        } else {
+            match on_collision {
+                Collision::Error => {
+                    let paths: Vec<_> = sources.iter().map(|s| s.display().to_string()).collect();
+                    bail!(
+                        "{} files are named {}: {}; choose another --on-collision",
+                        paths.len(),
+                        name.to_string_lossy(),
+                        paths.join(", ")
+                    );
+                }
+                Collision::Skip => {
+                    if sources[0] != source {
+                        warn!(
+                            "skip {}, {} is already linked as {}",
+                            source.display(),
+                            sources[0].display(),
+                            name.to_string_lossy()
+                        );
+                        continue;
+                    }
+                    target_dir.join(name)
+                }
+                Collision::Suffix => target_dir.join(suffixed_name(source)),
+                Collision::Mirror => unreachable!(),
+            }
+        };
+
+        if let Some(&i) = linked.get(&link) {
+            bail!(
+                "{} and {} would both be linked as {}",
+                links[i].source.display(),
+                source.display(),
+                link.display()
+            );
+        }
+        linked.insert(link.clone(), links.len());
+        links.push(Link {
+            source: source.clone(),
+            link,
+        });
     }
 
-    // make target dir become absolute
-    target_dir = target_dir.canonicalize()?;
-    let source_dir = source_directory.as_ref().canonicalize()?;
+    Ok(links)
+}
 
-    for entry in WalkDir::new(source_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file() && re.is_match(&e.path().to_string_lossy()))
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
     {
-        let path = entry.path();
+        unix_fs::symlink(original, link)
+    }
 
-        let link_name = target_dir.join(path.file_name().unwrap());
+    #[cfg(windows)]
+    {
+        windows_fs::symlink_file(original, link)
+    }
+}
 
-        if !link_name.exists() || overwrite {
-            #[cfg(unix)]
-            {
-                if let Err(e) = unix_fs::symlink(path, &link_name) {
-                    error!("Failed to create symlink: {}", e);
-                }
+/// Links files in the chosen mode and describes them for the manifest.
+struct Linker {
+    target_dir: PathBuf,
+    mode: Mode,
+    relative: bool,
+    overwrite: bool,
+    checksum: bool,
+    progress: ProgressBar,
+}
+
+impl Linker {
+    fn new(args: &RsoftArgs, target_dir: &Path, links: &[Link]) -> Self {
+        let progress = if args.mode == Mode::Symlink || args.dry_run {
+            ProgressBar::hidden()
+        } else {
+            let bytes = links
+                .iter()
+                .filter_map(|link| fs::metadata(&link.source).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            let style = ProgressStyle::with_template(
+                "{bar:40} {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {wide_msg}",
+            )
+            .unwrap();
+            ProgressBar::new(bytes).with_style(style)
+        };
+
+        Self {
+            target_dir: target_dir.to_path_buf(),
+            mode: args.mode,
+            relative: args.relative,
+            overwrite: args.overwrite,
+            checksum: args.checksum,
+            progress,
+        }
+    }
+
+    /// The path a symlink points to.
+    fn original(&self, link: &Link) -> PathBuf {
+        if self.relative {
+            relative_path(link.link.parent().unwrap(), &link.source)
+        } else {
+            link.source.clone()
+        }
+    }
+
+    /// Whether an existing file already is the link.
+    fn is_linked(&self, link: &Link, previous: Option<&Entry>) -> io::Result<bool> {
+        // copies are trusted to be up to date when both files have the recorded size
+        let is_current_copy = previous.is_some_and(|previous| {
+            let size = |path: &Path| fs::symlink_metadata(path).ok().map(|m| m.len());
+            previous.mode.is_copy()
+                && previous.source == link.source
+                && size(&link.link) == Some(previous.size)
+                && size(&link.source) == Some(previous.size)
+        });
+
+        Ok(match self.mode {
+            Mode::Symlink => {
+                fs::read_link(&link.link).is_ok_and(|target| target == self.original(link))
+            }
+            // or a copy in place of a hard link across filesystems
+            Mode::Hardlink => is_current_copy || mode::same_file(&link.link, &link.source)?,
+            Mode::Copy | Mode::Reflink => is_current_copy,
+        })
+    }
+
+    /// Creates a link, an existing file is only replaced with `overwrite`.
+    /// Returns `None` if the existing file is kept.
+    fn link(&self, link: &Link, previous: Option<&Entry>) -> io::Result<Option<Entry>> {
+        let Link { source, link: path } = link;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.progress.set_message(path.display().to_string());
+
+        if path.symlink_metadata().is_ok() {
+            if self.is_linked(link, previous)? {
+                info!("{:?} already exists.", path);
+                self.progress.inc(fs::metadata(source)?.len());
+                return match previous {
+                    Some(previous) if previous.mode.is_copy() => {
+                        if previous.checksum.is_some() || !self.checksum {
+                            Ok(Some(previous.clone()))
+                        } else {
+                            self.entry(link, previous.mode, None, None).map(Some)
+                        }
+                    }
+                    _ => self.entry(link, self.mode, None, previous).map(Some),
+                };
+            }
+            if !self.overwrite {
+                warn!("{:?} already exists, use -o to overwrite it", path);
+                return Ok(None);
             }
+            fs::remove_file(path)?;
+        }
+
+        let mut mode = self.mode;
+        let mut copied = None;
+        match self.mode {
+            Mode::Symlink => symlink(&self.original(link), path)?,
+            Mode::Hardlink => match fs::hard_link(source, path) {
+                Ok(()) => self.progress.inc(fs::metadata(source)?.len()),
+                Err(e) if mode::is_cross_device(&e) => {
+                    warn!("{:?} is on another filesystem, copying it", source);
+                    mode = Mode::Copy;
+                    copied = Some(mode::copy(source, path, &self.progress)?);
+                }
+                Err(e) => return Err(e),
+            },
+            Mode::Reflink => match mode::reflink(source, path) {
+                Ok(()) => self.progress.inc(fs::metadata(source)?.len()),
+                Err(e) => {
+                    warn!("failed to reflink {:?} ({e}), copying it", source);
+                    mode = Mode::Copy;
+                    copied = Some(mode::copy(source, path, &self.progress)?);
+                }
+            },
+            Mode::Copy => copied = Some(mode::copy(source, path, &self.progress)?),
+        }
+        info!("Created {} {:?} -> {:?}", mode.as_str(), path, source);
 
-            #[cfg(windows)]
-            {
-                if let Err(e) = windows_fs::symlink_file(path, &link_name) {
-                    error!("Failed to create symlink: {}", e);
+        self.entry(link, mode, copied, previous).map(Some)
+    }
+
+    /// The manifest entry of a link, `copied` is the checksum of the content that was copied.
+    /// With `checksum`, a copy is checked against its source. The checksum of the `previous`
+    /// entry is reused while the source keeps its size and modification time.
+    fn entry(
+        &self,
+        link: &Link,
+        mode: Mode,
+        copied: Option<String>,
+        previous: Option<&Entry>,
+    ) -> io::Result<Entry> {
+        let source_checksum = || -> io::Result<String> {
+            if let Some(previous) = previous {
+                if let Some(checksum) = previous.current_checksum(&link.source)? {
+                    return Ok(checksum.to_string());
                 }
             }
-            info!("Created symlink {:?} -> {:?}", link_name, path);
+            manifest::checksum(&link.source)
+        };
+
+        let checksum = if !self.checksum {
+            None
+        } else if mode.is_copy() {
+            let expected = match copied {
+                Some(checksum) => checksum,
+                None => source_checksum()?,
+            };
+            let checksum = manifest::checksum(&link.link)?;
+            if checksum != expected {
+                fs::remove_file(&link.link)?;
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("the copy has checksum {checksum} instead of {expected}, removed it"),
+                ));
+            }
+            Some(checksum)
         } else {
-            info!("Symlink {:?} already exists.", link_name);
+            Some(source_checksum()?)
+        };
+
+        Entry::new(
+            link.link
+                .strip_prefix(&self.target_dir)
+                .unwrap()
+                .to_path_buf(),
+            link.source.clone(),
+            mode,
+            checksum,
+        )
+    }
+}
+
+/// Prints the links of the manifest that are not as rsoft left them.
+fn verify(target_dir: &Path) -> Result<()> {
+    let manifest = Manifest::read(target_dir)?;
+    if manifest.is_empty() {
+        bail!("{} has no rsoft manifest", target_dir.display());
+    }
+
+    let (mut total, mut problems) = (0, 0);
+    for entry in manifest.entries() {
+        total += 1;
+        let status = manifest::check(target_dir, entry)?;
+        if status != Status::Ok {
+            problems += 1;
+            println!(
+                "{}\t{}\t{}",
+                status.as_str(),
+                entry.link.display(),
+                entry.source.display()
+            );
         }
     }
 
+    if problems > 0 {
+        bail!("{problems} of {total} links failed verification");
+    }
+    info!("all {total} links are ok");
     Ok(())
 }
+
+/// Removes the links of the manifest that are not planned any more or whose file is gone.
+/// Links that were replaced since, and copies whose source is gone, are left alone.
+fn prune(manifest: &mut Manifest, target_dir: &Path, links: &[Link], dry_run: bool) -> Result<()> {
+    let planned: HashMap<PathBuf, &Path> = links
+        .iter()
+        .map(|link| (link.link.clone(), link.source.as_path()))
+        .collect();
+
+    let stale: Vec<Entry> = manifest
+        .entries()
+        .filter(|entry| {
+            let link = target_dir.join(&entry.link);
+            planned.get(&link) != Some(&entry.source.as_path()) || !entry.source.exists()
+        })
+        .cloned()
+        .collect();
+
+    for entry in stale {
+        let link = target_dir.join(&entry.link);
+        match manifest::check(target_dir, &entry)? {
+            Status::Missing => {}
+            Status::Retargeted => {
+                warn!(
+                    "keep {}, it was replaced since it was linked",
+                    link.display()
+                );
+            }
+            Status::Broken if entry.mode.is_copy() => {
+                warn!(
+                    "keep {}, its source is gone and it may be the last copy",
+                    link.display()
+                );
+                continue;
+            }
+            Status::Ok | Status::Broken | Status::Changed => {
+                if dry_run {
+                    println!("remove {}", link.display());
+                    continue;
+                }
+                fs::remove_file(&link)
+                    .with_context(|| format!("failed to remove {}", link.display()))?;
+                info!("Removed {} {:?}", entry.mode.as_str(), link);
+
+                // directories of mirrored links that are empty now
+                for dir in link.ancestors().skip(1) {
+                    if dir == target_dir || fs::remove_dir(dir).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        manifest.remove(&entry.link);
+    }
+
+    Ok(())
+}
+
+pub fn rsoft(args: &RsoftArgs) -> Result<()> {
+    let selector = Selector::new(args)?;
+
+    let target_dir = match &args.target {
+        Some(target) => absolute(target)?,
+        None => env::current_dir()?,
+    };
+    if args.verify {
+        return verify(&target_dir);
+    }
+
+    let source = args.source.as_ref().unwrap();
+    let source_dir = source
+        .canonicalize()
+        .with_context(|| format!("failed to open {}", source.display()))?;
+
+    let mut walker = WalkDir::new(&source_dir)
+        .follow_links(args.follow_links)
+        .sort_by_file_name();
+    if let Some(max_depth) = args.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    let relative = |path: &Path| path.strip_prefix(&source_dir).unwrap().to_path_buf();
+    let mut files = Vec::new();
+    for result in walker.into_iter().filter_entry(|e| {
+        // links from an earlier run into a target inside the source
+        let is_target = target_dir != source_dir && e.path() == target_dir;
+        !is_target && (e.depth() == 0 || !selector.is_excluded(&relative(e.path())))
+    }) {
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("skip {e}");
+                continue;
+            }
+        };
+        // not WalkDir::min_depth, which would not prune excluded directories above it
+        let deep_enough = entry.depth() >= args.min_depth.unwrap_or_default();
+        if deep_enough && entry.path().is_file() && selector.is_selected(&relative(entry.path())) {
+            files.push(entry.into_path());
+        }
+    }
+
+    let links = plan(files, &source_dir, &target_dir, args.on_collision)?;
+
+    let mut manifest = Manifest::read(&target_dir)?;
+    if args.prune {
+        prune(&mut manifest, &target_dir, &links, args.dry_run)?;
+    }
+
+    let linker = Linker::new(args, &target_dir, &links);
+    let mut failed = 0;
+    for link in &links {
+        if link.link == link.source {
+            warn!(
+                "skip {}, the link would replace the file",
+                link.source.display()
+            );
+            continue;
+        }
+
+        if args.dry_run {
+            let original = match args.mode {
+                Mode::Symlink => linker.original(link),
+                _ => link.source.clone(),
+            };
+            println!("{} -> {}", link.link.display(), original.display());
+            continue;
+        }
+
+        let relative_link = link.link.strip_prefix(&target_dir).unwrap();
+        match linker.link(link, manifest.get(relative_link)) {
+            Ok(Some(entry)) => manifest.insert(entry),
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to {} {:?}: {}", args.mode.as_str(), link.link, e);
+                failed += 1;
+            }
+        }
+    }
+    linker.progress.finish_and_clear();
+
+    if !args.dry_run {
+        fs::create_dir_all(&target_dir)
+            .with_context(|| format!("failed to create {}", target_dir.display()))?;
+        manifest.write(&target_dir, args.manifest_format)?;
+    }
+
+    if failed > 0 {
+        bail!("failed to create {failed} of {} links", links.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/a/b/links"), Path::new("/a/c/d.bam")),
+            Path::new("../../c/d.bam")
+        );
+        assert_eq!(
+            relative_path(Path::new("/a"), Path::new("/a/d.bam")),
+            Path::new("d.bam")
+        );
+    }
+
+    #[test]
+    fn test_suffixed_name() {
+        assert_eq!(suffixed_name(Path::new("/x/run1/s.fq.gz")), "s_run1.fq.gz");
+        assert_eq!(suffixed_name(Path::new("/x/run1/README")), "README_run1");
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use xxhash_rust::xxh3::Xxh3;
+
+use super::mode::{self, Mode};
+
+const TSV_NAME: &str = ".rsoft_manifest.tsv";
+const JSON_NAME: &str = ".rsoft_manifest.json";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Tsv,
+    Json,
+}
+
+impl ManifestFormat {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::Tsv => TSV_NAME,
+            Self::Json => JSON_NAME,
+        }
+    }
+}
+
+/// Whether a file is a manifest, which rsoft never links.
+pub fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == TSV_NAME || name == JSON_NAME)
+}
+
+/// The xxh3 checksum of a file, as hex.
+pub fn checksum(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Box::new(Xxh3::new());
+    let mut buf = vec![0; 1 << 16];
+
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+
+    Ok(format!("{:016x}", hasher.digest()))
+}
+
+/// The modification time of a file in nanoseconds since the Unix epoch, if the platform has it.
+fn mtime(metadata: &fs::Metadata) -> Option<u64> {
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(mtime.as_nanos()).ok()
+}
+
+/// A link created by rsoft.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The link, relative to the target directory
+    pub link: PathBuf,
+    /// The absolute path of the linked file
+    pub source: PathBuf,
+    pub size: u64,
+    pub checksum: Option<String>,
+    /// Manifests written before there were modes only have symlinks
+    #[serde(default)]
+    pub mode: Mode,
+    /// Modification time of the source in nanoseconds, to tell whether its checksum is current
+    #[serde(default)]
+    pub mtime: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(
+        link: PathBuf,
+        source: PathBuf,
+        mode: Mode,
+        checksum: Option<String>,
+    ) -> io::Result<Self> {
+        let metadata = fs::metadata(&source)?;
+        Ok(Self {
+            link,
+            source,
+            size: metadata.len(),
+            checksum,
+            mode,
+            mtime: mtime(&metadata),
+        })
+    }
+
+    /// The recorded checksum, if `source` still is the linked file with the recorded
+    /// size and modification time, so it does not have to be read again.
+    pub fn current_checksum(&self, source: &Path) -> io::Result<Option<&str>> {
+        let metadata = fs::metadata(source)?;
+        let unchanged = self.source == source
+            && self.size == metadata.len()
+            && self.mtime.is_some()
+            && self.mtime == mtime(&metadata);
+        Ok(self.checksum.as_deref().filter(|_| unchanged))
+    }
+}
+
+/// The state of a link compared to its manifest entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// The link was removed
+    Missing,
+    /// The linked file was removed
+    Broken,
+    /// The link was replaced or points to another file
+    Retargeted,
+    /// The linked file has another size or checksum
+    Changed,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Missing => "missing",
+            Self::Broken => "broken",
+            Self::Retargeted => "retargeted",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+/// The links rsoft created in a target directory, kept there as TSV or JSON.
+#[derive(Debug, Default)]
+pub struct Manifest {
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Manifest {
+    /// Reads the manifest of a target directory, empty if there is none.
+    pub fn read(target_dir: &Path) -> Result<Self> {
+        let json = target_dir.join(JSON_NAME);
+        let tsv = target_dir.join(TSV_NAME);
+
+        let entries: Vec<Entry> = if json.exists() {
+            let reader = File::open(&json).map(BufReader::new)?;
+            serde_json::from_reader(reader)
+                .with_context(|| format!("invalid manifest {}", json.display()))?
+        } else if tsv.exists() {
+            let reader = File::open(&tsv).map(BufReader::new)?;
+            let mut entries = Vec::new();
+            for line in reader.lines().skip(1) {
+                let line = line?;
+                let fields: Vec<_> = line.split('\t').collect();
+                let (link, source, size, checksum, mode, mtime) = match fields[..] {
+                    [link, source, size, checksum] => {
+                        (link, source, size, checksum, "symlink", "-")
+                    }
+                    [link, source, size, checksum, mode] => {
+                        (link, source, size, checksum, mode, "-")
+                    }
+                    [link, source, size, checksum, mode, mtime] => {
+                        (link, source, size, checksum, mode, mtime)
+                    }
+                    _ => bail!("invalid manifest line in {}: {line}", tsv.display()),
+                };
+                entries.push(Entry {
+                    link: link.into(),
+                    source: source.into(),
+                    size: size
+                        .parse()
+                        .with_context(|| format!("invalid size in {}: {line}", tsv.display()))?,
+                    checksum: Some(checksum)
+                        .filter(|checksum| *checksum != "-")
+                        .map(String::from),
+                    mode: Mode::from_str(mode, false)
+                        .map_err(|e| anyhow::anyhow!("invalid mode in {}: {e}", tsv.display()))?,
+                    mtime: match mtime {
+                        "-" => None,
+                        mtime => Some(mtime.parse().with_context(|| {
+                            format!("invalid mtime in {}: {line}", tsv.display())
+                        })?),
+                    },
+                });
+            }
+            entries
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.link.clone(), entry))
+                .collect(),
+        })
+    }
+
+    /// Writes the manifest, replacing one in the other format.
+    pub fn write(&self, target_dir: &Path, format: ManifestFormat) -> Result<()> {
+        let dst = target_dir.join(format.file_name());
+        // written next to it and renamed over it, so a manifest that is a link
+        // into another directory is replaced instead of written through
+        let mut partial = dst.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        if partial.symlink_metadata().is_ok() {
+            fs::remove_file(&partial)?;
+        }
+        let mut writer = File::options()
+            .write(true)
+            .create_new(true)
+            .open(&partial)
+            .map(BufWriter::new)
+            .with_context(|| format!("failed to create {}", partial.display()))?;
+
+        match format {
+            ManifestFormat::Json => {
+                let entries: Vec<_> = self.entries().collect();
+                serde_json::to_writer_pretty(&mut writer, &entries)?;
+                writeln!(writer)?;
+            }
+            ManifestFormat::Tsv => {
+                writeln!(writer, "link\tsource\tsize\tchecksum\tmode\tmtime")?;
+                for entry in self.entries() {
+                    writeln!(
+                        writer,
+                        "{}\t{}\t{}\t{}\t{}\t{}",
+                        entry.link.display(),
+                        entry.source.display(),
+                        entry.size,
+                        entry.checksum.as_deref().unwrap_or("-"),
+                        entry.mode.as_str(),
+                        entry
+                            .mtime
+                            .map_or_else(|| "-".to_string(), |mtime| mtime.to_string())
+                    )?;
+                }
+            }
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&partial, &dst).with_context(|| format!("failed to write {}", dst.display()))?;
+
+        for other in [ManifestFormat::Tsv, ManifestFormat::Json] {
+            if other != format {
+                let other = target_dir.join(other.file_name());
+                if other.exists() {
+                    fs::remove_file(other)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, link: &Path) -> Option<&Entry> {
+        self.entries.get(link)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    pub fn insert(&mut self, entry: Entry) {
+        self.entries.insert(entry.link.clone(), entry);
+    }
+
+    pub fn remove(&mut self, link: &Path) -> Option<Entry> {
+        self.entries.remove(link)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Resolves `.` and `..` without following symlinks.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Compares a link in the target directory with its manifest entry.
+pub fn check(target_dir: &Path, entry: &Entry) -> io::Result<Status> {
+    let link = target_dir.join(&entry.link);
+    let Ok(metadata) = link.symlink_metadata() else {
+        return Ok(Status::Missing);
+    };
+
+    if entry.mode == Mode::Symlink {
+        let Ok(original) = fs::read_link(&link) else {
+            return Ok(Status::Retargeted);
+        };
+        let parent = link.parent().unwrap_or(target_dir);
+        if normalize(&parent.join(original)) != entry.source {
+            return Ok(Status::Retargeted);
+        }
+    } else if !metadata.is_file() {
+        return Ok(Status::Retargeted);
+    }
+
+    if !entry.source.exists() {
+        return Ok(Status::Broken);
+    }
+    if entry.mode == Mode::Hardlink && !mode::same_file(&link, &entry.source)? {
+        return Ok(Status::Retargeted);
+    }
+
+    if fs::metadata(&entry.source)?.len() != entry.size
+        || (entry.mode.is_copy() && metadata.len() != entry.size)
+    {
+        return Ok(Status::Changed);
+    }
+    if let Some(expected) = &entry.checksum {
+        // a copy has its own content, which has to match the source as well
+        let mut contents = vec![&entry.source];
+        if entry.mode.is_copy() {
+            contents.push(&link);
+        }
+        for content in contents {
+            if checksum(content)? != *expected {
+                return Ok(Status::Changed);
+            }
+        }
+    }
+
+    Ok(Status::Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut manifest = Manifest::default();
+        manifest.insert(Entry {
+            link: "run1/a.bam".into(),
+            source: "/data/run1/a.bam".into(),
+            size: 12,
+            checksum: Some("00000000000000ff".into()),
+            mode: Mode::Copy,
+            mtime: Some(1_700_000_000_123_456_789),
+        });
+        manifest.insert(Entry {
+            link: "b.bam".into(),
+            source: "/data/b.bam".into(),
+            size: 3,
+            checksum: None,
+            mode: Mode::Symlink,
+            mtime: None,
+        });
+
+        for format in [ManifestFormat::Tsv, ManifestFormat::Json] {
+            manifest.write(temp_dir.path(), format).unwrap();
+            let read = Manifest::read(temp_dir.path()).unwrap();
+            assert_eq!(
+                read.entries().collect::<Vec<_>>(),
+                manifest.entries().collect::<Vec<_>>()
+            );
+        }
+        assert!(!temp_dir.path().join(TSV_NAME).exists());
+    }
+}
@@ -0,0 +1,163 @@
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::Xxh3;
+
+/// How the target directory refers to a file.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Symbolic link
+    #[default]
+    Symlink,
+    /// Hard link, copied when the target is on another filesystem
+    Hardlink,
+    /// Copy of the file
+    Copy,
+    /// Copy-on-write clone (Btrfs, XFS), copied when the filesystem does not support it
+    Reflink,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Symlink => "symlink",
+            Self::Hardlink => "hardlink",
+            Self::Copy => "copy",
+            Self::Reflink => "reflink",
+        }
+    }
+
+    /// Whether the target directory holds its own copy of the content.
+    pub fn is_copy(&self) -> bool {
+        matches!(self, Self::Copy | Self::Reflink)
+    }
+}
+
+/// Whether a hard link failed because the target is on another filesystem.
+pub fn is_cross_device(e: &io::Error) -> bool {
+    // EXDEV on Linux and macOS, ERROR_NOT_SAME_DEVICE on Windows
+    #[cfg(unix)]
+    const CROSS_DEVICE: i32 = 18;
+    #[cfg(windows)]
+    const CROSS_DEVICE: i32 = 17;
+
+    e.raw_os_error() == Some(CROSS_DEVICE)
+}
+
+/// Whether two paths are the same file, e.g. hard links of each other.
+pub fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let (a, b) = (fs::metadata(a)?, fs::metadata(b)?);
+        Ok(a.dev() == b.dev() && a.ino() == b.ino())
+    }
+
+    #[cfg(not(unix))]
+    {
+        Ok(a.canonicalize()? == b.canonicalize()?)
+    }
+}
+
+/// A file next to `dst` to write into before it is renamed, so `dst` is never half copied.
+fn partial_path(dst: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(dst.file_name().unwrap_or_default());
+    name.push(".rsoft-partial");
+    dst.with_file_name(name)
+}
+
+/// Writes `dst` through a partial file, which is removed if `write` or the rename fails.
+fn write_through_partial<T>(
+    dst: &Path,
+    write: impl FnOnce(&Path) -> io::Result<T>,
+) -> io::Result<T> {
+    let partial = partial_path(dst);
+    let result = write(&partial).and_then(|value| {
+        fs::rename(&partial, dst)?;
+        Ok(value)
+    });
+    if result.is_err() && partial.symlink_metadata().is_ok() {
+        // the error that matters is the one of the copy, not of the cleanup
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Copies a file, advancing `progress` by its bytes. Returns the xxh3 checksum of the content.
+pub fn copy(src: &Path, dst: &Path, progress: &ProgressBar) -> io::Result<String> {
+    write_through_partial(dst, |partial| {
+        let mut reader = File::open(src)?;
+        let mut writer = File::create(partial)?;
+        let mut hasher = Box::new(Xxh3::new());
+        let mut buf = vec![0; 1 << 16];
+
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            writer.write_all(&buf[..n])?;
+            progress.inc(n as u64);
+        }
+
+        writer.sync_all()?;
+        fs::set_permissions(partial, reader.metadata()?.permissions())?;
+
+        Ok(format!("{:016x}", hasher.digest()))
+    })
+}
+
+/// Clones a file sharing its blocks, which only some filesystems support.
+pub fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        write_through_partial(dst, |partial| {
+            let reader = File::open(src)?;
+            let writer = File::create(partial)?;
+
+            // SAFETY: both file descriptors are open for the duration of the call
+            let ret = unsafe { libc::ioctl(writer.as_raw_fd(), libc::FICLONE, reader.as_raw_fd()) };
+            if ret != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            fs::set_permissions(partial, reader.metadata()?.permissions())
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (src, dst);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reflinks are only supported on Linux",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_removes_partial_on_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        // a directory opens but cannot be read, so the copy fails after creating the partial file
+        let src = temp_dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+        let dst = temp_dir.path().join("a.bam");
+
+        assert!(copy(&src, &dst, &ProgressBar::hidden()).is_err());
+        assert!(!dst.exists());
+        assert!(!partial_path(&dst).exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+}
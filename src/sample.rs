@@ -0,0 +1,191 @@
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Args, ValueHint};
+use log::info;
+use noodles_bam as bam;
+use noodles_fastq as fastq;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::BTreeSet;
+use std::io::BufRead;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+use crate::compression;
+use crate::extract::SequenceFormat;
+use crate::filter::{self, parse_fraction};
+use crate::sort;
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("amount").required(true).args(["count", "fraction"])))]
+pub struct SampleArgs {
+    /// FASTQ, FASTA or BAM input file, plain or compressed; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Number of reads to keep, or of templates for BAM input
+    #[arg(short = 'n', long = "count")]
+    count: Option<usize>,
+
+    /// Fraction of reads to keep, or of templates for BAM input (0-1)
+    #[arg(short = 'f', long = "fraction", value_parser = parse_fraction)]
+    fraction: Option<f64>,
+
+    /// Seed of the random sample, the same seed gives the same reads
+    #[arg(short = 's', long = "seed", default_value = "11")]
+    seed: u64,
+
+    /// Number of threads to compress BAM output
+    #[arg(short = 't', long = "threads", default_value = "1")]
+    threads: NonZeroUsize,
+}
+
+/// Hashes the read name, so all records of a template get the same value.
+fn template_hash(record: &bam::Record, seed: u64) -> u64 {
+    let name = record.name();
+    xxh3_64_with_seed(name.as_ref().map_or(&[], |name| name.as_bytes()), seed)
+}
+
+/// Maps a hash uniformly onto [0, 1).
+fn unit_interval(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Keeps the templates with the `count` smallest name hashes, which is a uniform
+/// sample of templates that only needs one hash per template in memory.
+fn smallest_hashes(
+    reader: &mut bam::io::Reader<Box<dyn BufRead>>,
+    count: usize,
+    seed: u64,
+) -> Result<BTreeSet<u64>> {
+    let mut hashes = BTreeSet::new();
+
+    for result in reader.records() {
+        let hash = template_hash(&result?, seed);
+        if hashes.len() < count || hashes.last().is_some_and(|&last| hash < last) {
+            hashes.insert(hash);
+            if hashes.len() > count {
+                hashes.pop_last();
+            }
+        }
+    }
+
+    Ok(hashes)
+}
+
+fn sample_bam(args: &SampleArgs, reader: Box<dyn BufRead>) -> Result<()> {
+    let mut reader = bam::io::Reader::from(reader);
+    let mut header = reader.read_header()?;
+
+    let keep: Box<dyn Fn(u64) -> bool> = match (args.count, args.fraction) {
+        (_, Some(fraction)) => Box::new(move |hash| unit_interval(hash) < fraction),
+        (Some(count), None) => {
+            if args.input.as_os_str() == "-" {
+                bail!("sampling a number of templates reads the BAM twice, it cannot be stdin");
+            }
+            let hashes = smallest_hashes(&mut reader, count, args.seed)?;
+
+            reader = compression::open(&args.input).map(bam::io::Reader::from)?;
+            header = reader.read_header()?;
+            Box::new(move |hash| hashes.contains(&hash))
+        }
+        (None, None) => unreachable!("clap requires --count or --fraction"),
+    };
+
+    let mut writer = sort::bam_writer(args.output.as_deref(), args.threads, None)?;
+    writer.write_header(&header)?;
+
+    let (mut total, mut kept) = (0u64, 0u64);
+    for result in reader.records() {
+        let record = result?;
+        total += 1;
+        if keep(template_hash(&record, args.seed)) {
+            kept += 1;
+            writer.write_record(&header, &record)?;
+        }
+    }
+
+    writer.finish(&header)?;
+    info!("kept {kept} of {total} records");
+
+    Ok(())
+}
+
+fn sample_sequences(
+    args: &SampleArgs,
+    reader: Box<dyn BufRead>,
+    format: SequenceFormat,
+) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut output = compression::create(args.output.as_ref())?;
+    let mut writer = filter::Writer::new(&mut output, format);
+    let mut total = 0;
+
+    match (args.count, args.fraction) {
+        (_, Some(fraction)) => {
+            let mut kept = 0;
+            filter::for_each_record(reader, format, |record| {
+                total += 1;
+                if rng.gen::<f64>() < fraction {
+                    kept += 1;
+                    writer.write_record(&record)?;
+                }
+                Ok(())
+            })?;
+            info!("kept {kept} of {total} reads");
+        }
+        (Some(count), None) => {
+            // reservoir sampling, the reads are written in input order
+            let mut reservoir: Vec<(usize, fastq::Record)> = Vec::with_capacity(count);
+            filter::for_each_record(reader, format, |record| {
+                if reservoir.len() < count {
+                    reservoir.push((total, record));
+                } else {
+                    let i = rng.gen_range(0..=total);
+                    if i < count {
+                        reservoir[i] = (total, record);
+                    }
+                }
+                total += 1;
+                Ok(())
+            })?;
+
+            reservoir.sort_unstable_by_key(|(i, _)| *i);
+            for (_, record) in &reservoir {
+                writer.write_record(record)?;
+            }
+            info!("kept {} of {total} reads", reservoir.len());
+        }
+        (None, None) => unreachable!("clap requires --count or --fraction"),
+    }
+
+    output.finish()?;
+    Ok(())
+}
+
+pub fn sample(args: &SampleArgs) -> Result<()> {
+    let mut reader = compression::open(&args.input)
+        .with_context(|| format!("failed to open {}", args.input.display()))?;
+
+    match reader.fill_buf()? {
+        buf if buf.starts_with(b"BAM\x01") => sample_bam(args, reader),
+        [b'>', ..] => sample_sequences(args, reader, SequenceFormat::Fasta),
+        [b'@', ..] | [] => sample_sequences(args, reader, SequenceFormat::Fastq),
+        _ => bail!("{} is not a FASTQ, FASTA or BAM file", args.input.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_interval() {
+        assert_eq!(unit_interval(0), 0.0);
+        assert!(unit_interval(u64::MAX) < 1.0);
+        assert_eq!(unit_interval(1 << 63), 0.5);
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{bail, Context, Result};
+use bio::alphabets::dna;
+use clap::{Args, ValueHint};
+use log::info;
+use noodles_core::{region::Interval, Position, Region};
+use noodles_fasta as fasta;
+use noodles_fastq as fastq;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::compression;
+use crate::extract::SequenceFormat;
+use crate::filter;
+
+#[derive(Args, Debug)]
+pub struct SeqArgs {
+    /// FASTQ or FASTA input file, plain or compressed; `-` for stdin.
+    /// With --bed, a FASTA file indexed by `rboss index`
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output file, compressed when it ends with .gz, .bgz or .zst [default: stdout]
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Extract the regions of a BED file, on the reverse strand when the strand column is '-'
+    #[arg(long = "bed", value_hint = ValueHint::FilePath)]
+    bed: Option<PathBuf>,
+
+    /// Reverse complement the reads, qualities are reversed
+    #[arg(short = 'r', long = "revcomp")]
+    revcomp: bool,
+
+    /// Convert bases to uppercase
+    #[arg(short = 'U', long = "upper", conflicts_with = "lower")]
+    upper: bool,
+
+    /// Convert bases to lowercase
+    #[arg(short = 'l', long = "lower")]
+    lower: bool,
+
+    /// Translate to protein with the standard genetic code, the output is FASTA
+    #[arg(short = 'T', long = "translate")]
+    translate: bool,
+
+    /// Reading frames to translate: 1, 2 and 3 on the forward strand, -1, -2 and -3 on the reverse strand.
+    /// Reads get a `_frame=N` suffix when there is more than one frame
+    #[arg(
+        short = 'f',
+        long = "frames",
+        requires = "translate",
+        value_delimiter = ',',
+        default_value = "1",
+        allow_hyphen_values = true,
+        value_parser = parse_frame
+    )]
+    frames: Vec<i8>,
+}
+
+fn parse_frame(s: &str) -> Result<i8, String> {
+    match s.parse::<i8>() {
+        Ok(frame @ (-3..=-1 | 1..=3)) => Ok(frame),
+        _ => Err(format!("invalid frame {s}, expected 1, 2, 3, -1, -2 or -3")),
+    }
+}
+
+/// Amino acids of the standard genetic code, with codons ordered by T, C, A, G.
+const CODON_TABLE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+fn translate_codon(codon: &[u8]) -> u8 {
+    let mut i = 0;
+    for &base in codon {
+        let n = match base.to_ascii_uppercase() {
+            b'T' | b'U' => 0,
+            b'C' => 1,
+            b'A' => 2,
+            b'G' => 3,
+            _ => return b'X',
+        };
+        i = i * 4 + n;
+    }
+    CODON_TABLE[i]
+}
+
+/// Translates a reading frame, stop codons are `*` and a trailing partial codon is dropped.
+fn translate(sequence: &[u8], frame: i8) -> Vec<u8> {
+    let offset = usize::from(frame.unsigned_abs() - 1);
+    let translate_from = |sequence: &[u8]| {
+        sequence
+            .get(offset..)
+            .unwrap_or_default()
+            .chunks_exact(3)
+            .map(translate_codon)
+            .collect()
+    };
+
+    if frame > 0 {
+        translate_from(sequence)
+    } else {
+        translate_from(&dna::revcomp(sequence))
+    }
+}
+
+/// A region of a BED line, with its name and whether it is on the reverse strand.
+fn parse_bed_line(line: &str) -> Result<Option<(Region, String, bool)>> {
+    if line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+    {
+        return Ok(None);
+    }
+
+    let fields: Vec<_> = line.split('\t').collect();
+    let [chrom, start, end, rest @ ..] = &fields[..] else {
+        bail!("invalid BED line: {line}");
+    };
+    let start: usize = start
+        .parse()
+        .with_context(|| format!("invalid BED start: {line}"))?;
+    let end: usize = end
+        .parse()
+        .with_context(|| format!("invalid BED end: {line}"))?;
+    if start >= end {
+        bail!("empty BED region: {line}");
+    }
+
+    // BED is 0-based and half-open, regions are 1-based and closed
+    let interval = Interval::from(Position::new(start + 1).unwrap()..=Position::new(end).unwrap());
+    let name = match rest.first() {
+        Some(name) if !name.is_empty() && *name != "." => name.to_string(),
+        _ => format!("{chrom}:{}-{end}", start + 1),
+    };
+    let reverse = rest.get(2) == Some(&"-");
+
+    Ok(Some((Region::new(*chrom, interval), name, reverse)))
+}
+
+/// Applies the sequence operations to a read and writes the result.
+fn process<W: io::Write>(
+    args: &SeqArgs,
+    writer: &mut filter::Writer<W>,
+    record: fastq::Record,
+) -> io::Result<()> {
+    let mut sequence = record.sequence().to_vec();
+    let mut quality_scores = record.quality_scores().to_vec();
+
+    if args.revcomp {
+        sequence = dna::revcomp(&sequence);
+        quality_scores.reverse();
+    }
+    if args.upper {
+        sequence.make_ascii_uppercase();
+    } else if args.lower {
+        sequence.make_ascii_lowercase();
+    }
+
+    if !args.translate {
+        let definition = fastq::record::Definition::new(record.name(), record.description());
+        return writer.write_record(&fastq::Record::new(definition, sequence, quality_scores));
+    }
+
+    for &frame in &args.frames {
+        let mut name = record.name().to_vec();
+        if args.frames.len() > 1 {
+            name.extend(format!("_frame={frame}").bytes());
+        }
+        let definition = fastq::record::Definition::new(name, record.description());
+        let protein = translate(&sequence, frame);
+        writer.write_record(&fastq::Record::new(definition, protein, Vec::new()))?;
+    }
+
+    Ok(())
+}
+
+fn bed_records(
+    args: &SeqArgs,
+    bed: &Path,
+    mut f: impl FnMut(fastq::Record) -> io::Result<()>,
+) -> Result<()> {
+    if args.input.as_os_str() == "-" {
+        bail!("--bed needs an indexed FASTA file, it cannot be stdin");
+    }
+    let mut reader = fasta::indexed_reader::Builder::default()
+        .build_from_path(&args.input)
+        .with_context(|| {
+            format!(
+                "failed to open {}, index it with `rboss index`",
+                args.input.display()
+            )
+        })?;
+
+    let lines = File::open(bed)
+        .map(BufReader::new)
+        .with_context(|| format!("failed to open {}", bed.display()))?
+        .lines();
+
+    for line in lines {
+        let Some((region, name, reverse)) = parse_bed_line(&line?)? else {
+            continue;
+        };
+        let record = reader
+            .query(&region)
+            .with_context(|| format!("failed to extract {region}"))?;
+
+        let mut sequence = record.sequence().as_ref().to_vec();
+        if reverse {
+            sequence = dna::revcomp(&sequence);
+        }
+        let definition = fastq::record::Definition::new(name, "");
+        f(fastq::Record::new(definition, sequence, Vec::new()))?;
+    }
+
+    Ok(())
+}
+
+pub fn seq(args: &SeqArgs) -> Result<()> {
+    let mut output = compression::create(args.output.as_ref())?;
+    let mut total = 0;
+
+    match &args.bed {
+        Some(bed) => {
+            let mut writer = filter::Writer::new(&mut output, SequenceFormat::Fasta);
+            bed_records(args, bed, |record| {
+                total += 1;
+                process(args, &mut writer, record)
+            })?;
+        }
+        None => {
+            let mut reader = compression::open(&args.input)
+                .with_context(|| format!("failed to open {}", args.input.display()))?;
+            let format = match reader.fill_buf()?.first() {
+                Some(b'>') => SequenceFormat::Fasta,
+                Some(b'@') | None => SequenceFormat::Fastq,
+                Some(_) => bail!("{} is not a FASTQ or FASTA file", args.input.display()),
+            };
+            let output_format = if args.translate {
+                SequenceFormat::Fasta
+            } else {
+                format
+            };
+
+            let mut writer = filter::Writer::new(&mut output, output_format);
+            filter::for_each_record(reader, format, |record| {
+                total += 1;
+                process(args, &mut writer, record)
+            })?;
+        }
+    }
+
+    output.finish()?;
+    info!("processed {total} sequences");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        // ATG GCC TAA G
+        let sequence = b"ATGGCCTAAG";
+        assert_eq!(translate(sequence, 1), b"MA*");
+        assert_eq!(translate(sequence, 2), b"WPK");
+        assert_eq!(translate(sequence, 3), b"GL");
+        // CTTAGGCCAT
+        assert_eq!(translate(sequence, -1), b"LRP");
+        assert_eq!(translate(b"atgNNN", 1), b"MX");
+    }
+}
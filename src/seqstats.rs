@@ -0,0 +1,361 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueHint};
+use noodles_bam as bam;
+use noodles_fasta as fasta;
+use noodles_fastq as fastq;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use crate::compression;
+
+#[derive(Args, Debug)]
+pub struct SeqstatsArgs {
+    /// FASTA, FASTQ or BAM input files, plain or compressed; `-` for stdin
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    inputs: Vec<PathBuf>,
+
+    /// Print the name, length, GC content and mean quality of every read
+    #[arg(long = "per-read", conflicts_with = "json")]
+    per_read: bool,
+
+    /// Print the per-file statistics as JSON
+    #[arg(long = "json")]
+    json: bool,
+
+    /// Number of files to read in parallel [default: number of CPUs]
+    #[arg(short = 't', long = "threads")]
+    threads: Option<NonZeroUsize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum Format {
+    Fasta,
+    Fastq,
+    Bam,
+}
+
+impl Format {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fasta => "FASTA",
+            Self::Fastq => "FASTQ",
+            Self::Bam => "BAM",
+        }
+    }
+}
+
+/// Calls `f` with the name, sequence and Phred qualities (without offset) of every read.
+/// BAM secondary and supplementary records are skipped. Returns `None` for an empty input.
+fn for_each_read<P, F>(src: P, mut f: F) -> io::Result<Option<Format>>
+where
+    P: AsRef<Path>,
+    F: FnMut(&[u8], &[u8], Option<&[u8]>) -> io::Result<()>,
+{
+    let mut reader = compression::open(src)?;
+
+    let format = match reader.fill_buf()? {
+        [] => return Ok(None),
+        [b'>', ..] => Format::Fasta,
+        [b'@', ..] => Format::Fastq,
+        buf if buf.starts_with(b"BAM\x01") => Format::Bam,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected FASTA, FASTQ or BAM input",
+            ))
+        }
+    };
+
+    match format {
+        Format::Fasta => {
+            for result in fasta::Reader::new(reader).records() {
+                let record = result?;
+                f(record.name(), record.sequence().as_ref(), None)?;
+            }
+        }
+        Format::Fastq => {
+            let mut quality_scores = Vec::new();
+            for result in fastq::Reader::new(reader).records() {
+                let record = result?;
+                quality_scores.clear();
+                quality_scores.extend(
+                    record
+                        .quality_scores()
+                        .iter()
+                        .map(|q| q.saturating_sub(b'!')),
+                );
+                f(record.name(), record.sequence(), Some(&quality_scores))?;
+            }
+        }
+        Format::Bam => {
+            let mut reader = bam::io::Reader::from(reader);
+            reader.read_header()?;
+
+            let mut sequence = Vec::new();
+            for result in reader.records() {
+                let record = result?;
+                let flags = record.flags();
+                if flags.is_secondary() || flags.is_supplementary() {
+                    continue;
+                }
+
+                sequence.clear();
+                sequence.extend(record.sequence().iter());
+
+                // a missing quality string is stored as 0xff
+                let quality_scores = record.quality_scores();
+                let quality_scores = match quality_scores.as_ref() {
+                    [] | [0xff, ..] => None,
+                    scores => Some(scores),
+                };
+
+                let name = record.name();
+                let name = name.as_ref().map_or(&b"*"[..], |name| name.as_bytes());
+                f(name, &sequence, quality_scores)?;
+            }
+        }
+    }
+
+    Ok(Some(format))
+}
+
+fn gc_count(sequence: &[u8]) -> usize {
+    sequence
+        .iter()
+        .filter(|base| matches!(base, b'G' | b'C' | b'g' | b'c'))
+        .count()
+}
+
+fn percent(n: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 * 100.0 / total as f64
+    }
+}
+
+/// The length L such that reads of length >= L hold at least `x` percent of the bases.
+/// `lengths` must be sorted in descending order.
+fn nx(lengths: &[u64], total: u64, x: u64) -> u64 {
+    let mut sum = 0;
+    for &len in lengths {
+        sum += len;
+        if sum * 100 >= total * x {
+            return len;
+        }
+    }
+    0
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    lengths: Vec<u64>,
+    gc_bases: u64,
+    quality_bases: u64,
+    quality_sum: u64,
+    q20_bases: u64,
+    q30_bases: u64,
+}
+
+impl Accumulator {
+    fn add(&mut self, sequence: &[u8], quality_scores: Option<&[u8]>) {
+        self.lengths.push(sequence.len() as u64);
+        self.gc_bases += gc_count(sequence) as u64;
+
+        for &q in quality_scores.unwrap_or_default() {
+            self.quality_bases += 1;
+            self.quality_sum += u64::from(q);
+            self.q20_bases += u64::from(q >= 20);
+            self.q30_bases += u64::from(q >= 30);
+        }
+    }
+
+    fn finish(mut self, file: String, format: Option<Format>) -> FileStats {
+        self.lengths.sort_unstable_by(|a, b| b.cmp(a));
+
+        let num_seqs = self.lengths.len() as u64;
+        let sum_len = self.lengths.iter().sum();
+        let has_qualities = self.quality_bases > 0;
+        let quality_percent = |n| has_qualities.then(|| percent(n, self.quality_bases));
+
+        FileStats {
+            file,
+            format,
+            num_seqs,
+            sum_len,
+            min_len: self.lengths.last().copied().unwrap_or(0),
+            avg_len: if num_seqs == 0 {
+                0.0
+            } else {
+                sum_len as f64 / num_seqs as f64
+            },
+            max_len: self.lengths.first().copied().unwrap_or(0),
+            n50: nx(&self.lengths, sum_len, 50),
+            n90: nx(&self.lengths, sum_len, 90),
+            gc: percent(self.gc_bases, sum_len),
+            mean_qual: has_qualities.then(|| self.quality_sum as f64 / self.quality_bases as f64),
+            q20: quality_percent(self.q20_bases),
+            q30: quality_percent(self.q30_bases),
+        }
+    }
+}
+
+/// Statistics of one input file. GC is a percentage of all bases, Q20/Q30 of the
+/// bases that have a quality; qualities are `None` for FASTA input.
+#[derive(Debug, Serialize)]
+struct FileStats {
+    file: String,
+    format: Option<Format>,
+    num_seqs: u64,
+    sum_len: u64,
+    min_len: u64,
+    avg_len: f64,
+    max_len: u64,
+    n50: u64,
+    n90: u64,
+    gc: f64,
+    mean_qual: Option<f64>,
+    q20: Option<f64>,
+    q30: Option<f64>,
+}
+
+const HEADER: &str =
+    "file\tformat\tnum_seqs\tsum_len\tmin_len\tavg_len\tmax_len\tn50\tn90\tgc\tmean_qual\tq20\tq30";
+
+fn fmt_optional(value: Option<f64>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| format!("{value:.2}"))
+}
+
+impl FileStats {
+    fn write_tsv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}\t{:.2}\t{}\t{}\t{}",
+            self.file,
+            self.format.map_or("-", |format| format.as_str()),
+            self.num_seqs,
+            self.sum_len,
+            self.min_len,
+            self.avg_len,
+            self.max_len,
+            self.n50,
+            self.n90,
+            self.gc,
+            fmt_optional(self.mean_qual),
+            fmt_optional(self.q20),
+            fmt_optional(self.q30),
+        )
+    }
+}
+
+fn file_stats(src: &Path) -> Result<FileStats> {
+    let mut accumulator = Accumulator::default();
+
+    let format = for_each_read(src, |_, sequence, quality_scores| {
+        accumulator.add(sequence, quality_scores);
+        Ok(())
+    })
+    .with_context(|| format!("failed to read {}", src.display()))?;
+
+    Ok(accumulator.finish(src.display().to_string(), format))
+}
+
+fn write_per_read<W: Write>(src: &Path, writer: &mut W) -> Result<()> {
+    for_each_read(src, |name, sequence, quality_scores| {
+        let gc = percent(gc_count(sequence) as u64, sequence.len() as u64);
+        let mean_qual = quality_scores
+            .filter(|scores| !scores.is_empty())
+            .map(|scores| {
+                scores.iter().map(|&q| u64::from(q)).sum::<u64>() as f64 / scores.len() as f64
+            });
+
+        writer.write_all(src.display().to_string().as_bytes())?;
+        writer.write_all(b"\t")?;
+        writer.write_all(name)?;
+        writeln!(
+            writer,
+            "\t{}\t{gc:.2}\t{}",
+            sequence.len(),
+            fmt_optional(mean_qual)
+        )
+    })
+    .with_context(|| format!("failed to read {}", src.display()))?;
+
+    Ok(())
+}
+
+pub fn seqstats(args: &SeqstatsArgs) -> Result<()> {
+    let mut writer = BufWriter::new(io::stdout().lock());
+
+    if args.per_read {
+        // streamed in input order, one file after the other
+        writeln!(writer, "file\tname\tlength\tgc\tmean_qual")?;
+        for src in &args.inputs {
+            write_per_read(src, &mut writer)?;
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let threads = args
+        .threads
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?;
+    let stats = pool.install(|| {
+        args.inputs
+            .par_iter()
+            .map(|src| file_stats(src))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    if args.json {
+        serde_json::to_writer_pretty(&mut writer, &stats)?;
+        writeln!(writer)?;
+    } else {
+        writeln!(writer, "{HEADER}")?;
+        for file_stats in &stats {
+            file_stats.write_tsv(&mut writer)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nx() {
+        let lengths = [8, 6, 4, 2];
+        assert_eq!(nx(&lengths, 20, 50), 6);
+        assert_eq!(nx(&lengths, 20, 90), 4);
+        assert_eq!(nx(&lengths, 20, 100), 2);
+        assert_eq!(nx(&[], 0, 50), 0);
+    }
+
+    #[test]
+    fn test_accumulator() {
+        let mut accumulator = Accumulator::default();
+        accumulator.add(b"ACGT", Some(&[10, 20, 30, 40]));
+        accumulator.add(b"GG", Some(&[30, 30]));
+
+        let stats = accumulator.finish("reads.fq".to_string(), Some(Format::Fastq));
+        assert_eq!(stats.num_seqs, 2);
+        assert_eq!(stats.sum_len, 6);
+        assert_eq!((stats.min_len, stats.max_len), (2, 4));
+        assert_eq!(stats.n50, 4);
+        assert_eq!(stats.gc, 4.0 * 100.0 / 6.0);
+        assert_eq!(stats.mean_qual, Some(160.0 / 6.0));
+        assert_eq!(stats.q20, Some(5.0 * 100.0 / 6.0));
+        assert_eq!(stats.q30, Some(4.0 * 100.0 / 6.0));
+    }
+}
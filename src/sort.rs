@@ -0,0 +1,624 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueHint};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    env, fs,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use noodles_bam as bam;
+use noodles_bgzf as bgzf;
+use noodles_sam::{
+    self as sam,
+    alignment::record::data::field::{Tag, Value},
+    header::record::value::{
+        map::{self, header::sort_order, header::tag},
+        Map,
+    },
+};
+
+use crate::extract::AlignmentWriter;
+
+/// Memory used to sort records before spilling them to temporary files.
+const DEFAULT_MEMORY: usize = 768 << 20;
+
+/// Rough per-record cost of the in-memory buffer on top of the record data.
+const RECORD_OVERHEAD: usize = 96;
+
+/// Temporary files merged at once, well below common limits of open files.
+const MAX_OPEN_RUNS: usize = 64;
+
+#[derive(Args, Debug)]
+pub struct SortArgs {
+    /// Bam input file
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Output BAM file. Default is stdout
+    #[arg(short = 'o', long = "output", value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+
+    /// Sort by query name instead of coordinate
+    #[arg(short = 'n', long = "name", default_value = "false")]
+    name: bool,
+
+    /// Sort by the value of this data field (e.g. HP), then by coordinate or query name
+    #[arg(long = "tag", value_parser = parse_tag)]
+    tag: Option<Tag>,
+
+    /// Memory for sorting in memory before spilling to temporary files, e.g. 512M or 2G
+    #[arg(short = 'm', long = "memory", default_value = "768M", value_parser = parse_memory)]
+    memory: usize,
+
+    /// Directory for temporary files. Default is the system temporary directory
+    #[arg(short = 'T', long = "tmp-dir", value_hint = ValueHint::DirPath)]
+    tmp_dir: Option<PathBuf>,
+
+    /// Compression level of the output BAM (0-9)
+    #[arg(short = 'l', long = "level", value_parser = clap::value_parser!(u8).range(0..=9))]
+    compression_level: Option<u8>,
+
+    /// Number of threads to decompress the input and compress the output
+    #[arg(short = 't', long = "threads", default_value = "1")]
+    threads: NonZeroUsize,
+}
+
+pub fn parse_tag(s: &str) -> Result<Tag, String> {
+    match s.as_bytes() {
+        [a, b] => Ok(Tag::new(*a, *b)),
+        _ => Err(format!("invalid tag {s}, expected two characters")),
+    }
+}
+
+/// Parses a memory size with an optional K, M or G suffix.
+fn parse_memory(s: &str) -> Result<usize, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+
+    let shift = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" => 10,
+        "M" | "MB" => 20,
+        "G" | "GB" => 30,
+        _ => return Err(format!("invalid memory size {s}, expected e.g. 512M or 2G")),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("invalid memory size {s}, expected e.g. 512M or 2G"))
+}
+
+/// An `f64` with a total order, for sorting on float data fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Float(f64);
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A data field value as a sort key. Records without the field come first.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TagValue {
+    Missing,
+    Int(i64),
+    Float(Float),
+    Text(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Position {
+    /// Reference sequence ID (unplaced reads last), 0-based start and strand.
+    Coordinate(usize, usize, bool),
+    /// Query name, then first segment before last segment.
+    Name(Vec<u8>, u16),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    tag: Option<TagValue>,
+    position: Position,
+}
+
+impl SortKey {
+    fn len(&self) -> usize {
+        let tag_len = match &self.tag {
+            Some(TagValue::Text(s)) => s.len(),
+            _ => 0,
+        };
+        let name_len = match &self.position {
+            Position::Name(name, _) => name.len(),
+            Position::Coordinate(..) => 0,
+        };
+
+        tag_len + name_len
+    }
+}
+
+fn coordinate(record: &bam::Record) -> io::Result<(usize, usize)> {
+    let reference_sequence_id = record
+        .reference_sequence_id()
+        .transpose()?
+        .unwrap_or(usize::MAX);
+    let start = record
+        .alignment_start()
+        .transpose()?
+        .map(usize::from)
+        .unwrap_or_default();
+
+    Ok((reference_sequence_id, start))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortOrder {
+    by_name: bool,
+    tag: Option<Tag>,
+}
+
+impl SortOrder {
+    fn key(&self, record: &bam::Record) -> io::Result<SortKey> {
+        let tag = self
+            .tag
+            .map(|tag| -> io::Result<TagValue> {
+                let value = match record.data().get(&tag).transpose()? {
+                    None | Some(Value::Array(_)) => TagValue::Missing,
+                    Some(Value::Character(c)) => TagValue::Text(vec![c]),
+                    Some(Value::String(s) | Value::Hex(s)) => TagValue::Text(s.to_vec()),
+                    Some(Value::Float(n)) => TagValue::Float(Float(f64::from(n))),
+                    Some(value) => value.as_int().map_or(TagValue::Missing, TagValue::Int),
+                };
+                Ok(value)
+            })
+            .transpose()?;
+
+        let flags = record.flags();
+
+        let position = if self.by_name {
+            let name = record.name().map(|name| name.as_ref().to_vec());
+            let segment = u16::from(flags) & (0x40 | 0x80);
+            Position::Name(name.unwrap_or_default(), segment)
+        } else {
+            let (reference_sequence_id, start) = coordinate(record)?;
+            Position::Coordinate(
+                reference_sequence_id,
+                start,
+                flags.is_reverse_complemented(),
+            )
+        };
+
+        Ok(SortKey { tag, position })
+    }
+
+    /// Records the sort order in the `@HD` header line.
+    fn set_header(&self, header: &mut sam::Header) {
+        let hd = header
+            .header_mut()
+            .get_or_insert_with(|| Map::<map::Header>::new(Default::default()));
+        let other_fields = hd.other_fields_mut();
+
+        let sort_order = match (self.tag, self.by_name) {
+            (Some(_), _) => sort_order::UNKNOWN,
+            (None, true) => sort_order::QUERY_NAME,
+            (None, false) => sort_order::COORDINATE,
+        };
+        other_fields.insert(tag::SORT_ORDER, sort_order.into());
+
+        // names are compared bytewise, not in the natural order of samtools sort -n
+        let by = if self.by_name {
+            "queryname:lexicographical"
+        } else {
+            "coordinate"
+        };
+        match self.tag {
+            Some(data_tag) => {
+                let [a, b] = *data_tag.as_ref();
+                let subsort = format!("unknown:tag:{}{}:{by}", char::from(a), char::from(b));
+                other_fields.insert(tag::SUBSORT_ORDER, subsort.into());
+            }
+            None if self.by_name => {
+                other_fields.insert(tag::SUBSORT_ORDER, by.into());
+            }
+            None => {
+                other_fields.shift_remove(&tag::SUBSORT_ORDER);
+            }
+        }
+
+        other_fields.shift_remove(&tag::GROUP_ORDER);
+    }
+}
+
+fn record_size(record: &bam::Record) -> usize {
+    let name_len = record.name().map_or(0, |name| name.as_ref().len());
+
+    name_len
+        + record.cigar().as_ref().len()
+        + record.sequence().as_ref().len()
+        + record.quality_scores().as_ref().len()
+        + record.data().as_ref().len()
+}
+
+/// A sorted run of records: a temporary BAM file or the last in-memory buffer.
+enum Run {
+    File(bam::io::Reader<bgzf::Reader<File>>),
+    Memory(std::vec::IntoIter<(SortKey, bam::Record)>),
+}
+
+impl Run {
+    fn next(&mut self, order: &SortOrder) -> io::Result<Option<(SortKey, bam::Record)>> {
+        match self {
+            Self::File(reader) => {
+                let mut record = bam::Record::default();
+                if reader.read_record(&mut record)? == 0 {
+                    return Ok(None);
+                }
+                Ok(Some((order.key(&record)?, record)))
+            }
+            Self::Memory(records) => Ok(records.next()),
+        }
+    }
+}
+
+/// External merge sort of BAM records.
+#[derive(Debug)]
+pub struct Sorter {
+    order: SortOrder,
+    memory: usize,
+    tmp_dir: Option<PathBuf>,
+    compression_level: Option<u8>,
+    threads: NonZeroUsize,
+    max_open_runs: usize,
+}
+
+impl Default for Sorter {
+    fn default() -> Self {
+        Self {
+            order: SortOrder::default(),
+            memory: DEFAULT_MEMORY,
+            tmp_dir: None,
+            compression_level: None,
+            threads: NonZeroUsize::MIN,
+            max_open_runs: MAX_OPEN_RUNS,
+        }
+    }
+}
+
+impl Sorter {
+    /// A coordinate sorter with default settings, for `index --sort`.
+    pub fn coordinate(threads: NonZeroUsize) -> Self {
+        Self {
+            threads,
+            ..Default::default()
+        }
+    }
+
+    fn writer(&self, dst: Option<&Path>) -> Result<AlignmentWriter> {
+        bam_writer(dst, self.threads, self.compression_level)
+    }
+
+    /// A temporary BAM file, compressed fast since it is read back once.
+    fn run_writer(dst: &Path) -> io::Result<bam::io::Writer<bgzf::Writer<BufWriter<File>>>> {
+        let file = File::create(dst).map(BufWriter::new)?;
+        Ok(bam::io::Writer::from(
+            bgzf::writer::Builder::default()
+                .set_compression_level(bgzf::writer::CompressionLevel::fast())
+                .build_with_writer(file),
+        ))
+    }
+
+    /// Sorts and writes the buffered records to a temporary BAM file.
+    fn spill(
+        &self,
+        header: &sam::Header,
+        records: &mut Vec<(SortKey, bam::Record)>,
+        dst: PathBuf,
+    ) -> io::Result<PathBuf> {
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut writer = Self::run_writer(&dst)?;
+        writer.write_header(header)?;
+        for (_, record) in records.drain(..) {
+            writer.write_record(header, &record)?;
+        }
+        writer.into_inner().finish()?.flush()?;
+
+        Ok(dst)
+    }
+
+    fn open_runs(chunks: &[PathBuf]) -> io::Result<Vec<Run>> {
+        let mut runs = Vec::with_capacity(chunks.len() + 1);
+        for chunk in chunks {
+            let mut reader = File::open(chunk).map(bam::io::Reader::new)?;
+            reader.read_header()?;
+            runs.push(Run::File(reader));
+        }
+        Ok(runs)
+    }
+
+    /// Merges sorted runs, passing the records to `write` in order.
+    fn merge<F>(&self, mut runs: Vec<Run>, mut write: F) -> io::Result<()>
+    where
+        F: FnMut(&bam::Record) -> io::Result<()>,
+    {
+        // ties go to the earlier run, so the sort is stable
+        let mut heads = Vec::with_capacity(runs.len());
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (i, run) in runs.iter_mut().enumerate() {
+            let head = run.next(&self.order)?;
+            if let Some((key, _)) = &head {
+                heap.push(Reverse((key.clone(), i)));
+            }
+            heads.push(head.map(|(_, record)| record));
+        }
+
+        while let Some(Reverse((_, i))) = heap.pop() {
+            if let Some(record) = heads[i].take() {
+                write(&record)?;
+            }
+
+            if let Some((key, record)) = runs[i].next(&self.order)? {
+                heap.push(Reverse((key, i)));
+                heads[i] = Some(record);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merges consecutive temporary files into one, so fewer files are open in the next merge.
+    fn merge_files(
+        &self,
+        header: &sam::Header,
+        chunks: &[PathBuf],
+        dst: PathBuf,
+    ) -> io::Result<PathBuf> {
+        let mut writer = Self::run_writer(&dst)?;
+        writer.write_header(header)?;
+        self.merge(Self::open_runs(chunks)?, |record| {
+            writer.write_record(header, record)
+        })?;
+        writer.into_inner().finish()?.flush()?;
+
+        for chunk in chunks {
+            fs::remove_file(chunk)?;
+        }
+
+        Ok(dst)
+    }
+
+    pub fn sort_bam<P: AsRef<Path>>(&self, src: P, dst: Option<&Path>) -> Result<()> {
+        let src = src.as_ref();
+        let mut reader = bgzf::reader::Builder::default()
+            .set_worker_count(self.threads)
+            .build_from_path(src)
+            .map(bam::io::Reader::from)
+            .with_context(|| format!("failed to open {}", src.display()))?;
+        let mut header = reader.read_header()?;
+        self.order.set_header(&mut header);
+
+        let tmp_dir = self.tmp_dir.clone().unwrap_or_else(env::temp_dir);
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("rboss-sort.")
+            .tempdir_in(&tmp_dir)
+            .with_context(|| format!("failed to create a directory in {}", tmp_dir.display()))?;
+
+        let mut chunks = Vec::new();
+        let mut records = Vec::new();
+        let mut size = 0;
+        let mut record = bam::Record::default();
+
+        while reader.read_record(&mut record)? != 0 {
+            let key = self.order.key(&record)?;
+            size += record_size(&record) + key.len() + RECORD_OVERHEAD;
+            records.push((key, mem::take(&mut record)));
+
+            if size >= self.memory {
+                let dst = tmp_dir.path().join(format!("{}.bam", chunks.len()));
+                chunks.push(self.spill(&header, &mut records, dst)?);
+                size = 0;
+            }
+        }
+
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        info!(
+            "sorting {} with {} temporary files",
+            src.display(),
+            chunks.len()
+        );
+
+        // with the in-memory run, every file is open at once in the final merge
+        let mut pass = 0;
+        while chunks.len() >= self.max_open_runs {
+            let mut merged = Vec::new();
+            for (i, batch) in chunks.chunks(self.max_open_runs).enumerate() {
+                let dst = tmp_dir.path().join(format!("merged.{pass}.{i}.bam"));
+                merged.push(self.merge_files(&header, batch, dst)?);
+            }
+            chunks = merged;
+            pass += 1;
+        }
+
+        let mut runs = Self::open_runs(&chunks)?;
+        runs.push(Run::Memory(records.into_iter()));
+
+        let mut writer = self.writer(dst)?;
+        writer.write_header(&header)?;
+        self.merge(runs, |record| writer.write_record(&header, record))?;
+        writer.finish(&header)?;
+
+        Ok(())
+    }
+}
+
+/// Fails for a compression level with multiple threads, which always compress at the default level.
+pub fn check_compression(threads: NonZeroUsize, compression_level: Option<u8>) -> Result<()> {
+    if compression_level.is_some() && threads.get() > 1 {
+        bail!("-l/--level cannot be combined with more than one thread (-t)");
+    }
+    Ok(())
+}
+
+/// Creates a BAM writer to `dst`, or stdout, compressing with `threads` threads.
+pub fn bam_writer(
+    dst: Option<&Path>,
+    threads: NonZeroUsize,
+    compression_level: Option<u8>,
+) -> Result<AlignmentWriter> {
+    check_compression(threads, compression_level)?;
+
+    let file: Box<dyn io::Write + Send> = match dst {
+        Some(dst) => Box::new(
+            File::create(dst).with_context(|| format!("failed to create {}", dst.display()))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+
+    if threads.get() > 1 {
+        // the compressed blocks are written whole, so the file is not buffered
+        return Ok(AlignmentWriter::MultithreadedBam(bam::io::Writer::from(
+            bgzf::MultithreadedWriter::with_worker_count(threads, file),
+        )));
+    }
+
+    let mut builder = bgzf::writer::Builder::default();
+    if let Some(level) = compression_level {
+        builder = builder.set_compression_level(level.try_into()?);
+    }
+    let file: Box<dyn io::Write + Send> = Box::new(BufWriter::new(file));
+    Ok(AlignmentWriter::Bam(bam::io::Writer::from(
+        builder.build_with_writer(file),
+    )))
+}
+
+/// Checks that the records of a BAM are in coordinate order, whatever the header says.
+pub fn is_coordinate_sorted<P: AsRef<Path>>(src: P, threads: NonZeroUsize) -> io::Result<bool> {
+    let mut reader = bgzf::reader::Builder::default()
+        .set_worker_count(threads)
+        .build_from_path(src)
+        .map(bam::io::Reader::from)?;
+    reader.read_header()?;
+
+    let mut record = bam::Record::default();
+    let mut last = (0, 0);
+
+    while reader.read_record(&mut record)? != 0 {
+        let current = coordinate(&record)?;
+        if current < last {
+            return Ok(false);
+        }
+        last = current;
+    }
+
+    Ok(true)
+}
+
+pub fn sort(args: &SortArgs) -> Result<()> {
+    // before sorting, and before an existing output could be removed on error
+    check_compression(args.threads, args.compression_level)?;
+
+    let sorter = Sorter {
+        order: SortOrder {
+            by_name: args.name,
+            tag: args.tag,
+        },
+        memory: args.memory,
+        tmp_dir: args.tmp_dir.clone(),
+        compression_level: args.compression_level,
+        threads: args.threads,
+        ..Default::default()
+    };
+
+    let dst = args.output.as_deref();
+    if let Err(e) = sorter.sort_bam(&args.input, dst) {
+        // only a partial file is removed, not e.g. /dev/stdout
+        if let Some(dst) = dst.filter(|dst| dst.symlink_metadata().is_ok_and(|m| m.is_file())) {
+            let _ = fs::remove_file(dst);
+        }
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("4K"), Ok(4096));
+        assert_eq!(parse_memory("768M"), Ok(768 << 20));
+        assert_eq!(parse_memory("2gb"), Ok(2 << 30));
+        assert!(parse_memory("0").is_err());
+        assert!(parse_memory("1T").is_err());
+        assert!(parse_memory("M").is_err());
+    }
+
+    #[test]
+    fn test_sort_merges_in_batches() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let by_name = temp_dir.path().join("name.bam");
+        let by_coordinate = temp_dir.path().join("coordinate.bam");
+
+        // every record is spilled, and no more than two runs are merged at once
+        let mut sorter = Sorter {
+            order: SortOrder {
+                by_name: true,
+                tag: None,
+            },
+            memory: 1,
+            tmp_dir: Some(temp_dir.path().to_path_buf()),
+            max_open_runs: 2,
+            ..Default::default()
+        };
+        sorter
+            .sort_bam("tests/data/reads.bam", Some(&by_name))
+            .unwrap();
+        sorter.order.by_name = false;
+        sorter.sort_bam(&by_name, Some(&by_coordinate)).unwrap();
+
+        let records = |src: &Path| -> Vec<bam::Record> {
+            let mut reader = bam::io::reader::Builder.build_from_path(src).unwrap();
+            reader.read_header().unwrap();
+            reader.records().map(Result::unwrap).collect()
+        };
+        let names: Vec<_> = records(&by_name)
+            .iter()
+            .map(|record| record.name().unwrap().as_bytes().to_vec())
+            .collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert_eq!(records(&by_coordinate).len(), 6);
+        assert!(is_coordinate_sorted(&by_coordinate, NonZeroUsize::MIN).unwrap());
+
+        // only the two outputs are left
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_tag_value_order() {
+        assert!(TagValue::Missing < TagValue::Int(-1));
+        assert!(TagValue::Int(2) < TagValue::Int(10));
+        assert!(TagValue::Float(Float(-0.5)) < TagValue::Float(Float(0.5)));
+        assert!(TagValue::Text(b"10".to_vec()) < TagValue::Text(b"9".to_vec()));
+    }
+}
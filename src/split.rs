@@ -0,0 +1,288 @@
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Args, ValueEnum, ValueHint};
+use log::info;
+use noodles_fastq as fastq;
+use regex::bytes::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use crate::compression;
+use crate::extract::SequenceFormat;
+use crate::filter;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compress {
+    /// bgzip, readable by gzip
+    Gz,
+    Zst,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("by").required(true).args(["parts", "reads", "bases", "pattern"])))]
+pub struct SplitArgs {
+    /// FASTQ or FASTA input file, plain or compressed; `-` for stdin
+    #[arg(value_hint = ValueHint::FilePath)]
+    input: PathBuf,
+
+    /// Split into this many parts, dealing reads out in turn
+    #[arg(short = 'n', long = "parts")]
+    parts: Option<NonZeroUsize>,
+
+    /// Split into parts of this many reads
+    #[arg(short = 'r', long = "reads")]
+    reads: Option<NonZeroUsize>,
+
+    /// Split into parts of at most this many bases, a part holds at least one read
+    #[arg(short = 'b', long = "bases")]
+    bases: Option<NonZeroUsize>,
+
+    /// Split by a regex on the read header (name and description), e.g. 'BC=(\w+)'.
+    /// The first capture group, or the whole match, names the part
+    #[arg(long = "by-name")]
+    pattern: Option<Regex>,
+
+    /// Directory of the parts
+    #[arg(short = 'O', long = "out-dir", default_value = ".", value_hint = ValueHint::DirPath)]
+    out_dir: PathBuf,
+
+    /// File name prefix of the parts [default: the input file name without extensions]
+    #[arg(short = 'p', long = "prefix")]
+    prefix: Option<String>,
+
+    /// Compress the parts
+    #[arg(short = 'z', long = "compress", value_enum)]
+    compress: Option<Compress>,
+}
+
+impl SplitArgs {
+    fn prefix(&self) -> String {
+        if let Some(prefix) = &self.prefix {
+            return prefix.clone();
+        }
+
+        if self.input.as_os_str() == "-" {
+            return "stdin".to_string();
+        }
+
+        let mut path = self.input.as_path();
+        for extensions in [
+            &["gz", "bgz", "zst"][..],
+            &["fq", "fastq", "fa", "fasta", "fna"][..],
+        ] {
+            let ext = path.extension().and_then(|ext| ext.to_str());
+            if ext.is_some_and(|ext| extensions.contains(&ext)) {
+                path = Path::new(path.file_stem().unwrap_or_default());
+            }
+        }
+
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "split".to_string())
+    }
+}
+
+/// Makes a pattern match usable as part of a file name.
+fn sanitize(name: &[u8]) -> String {
+    String::from_utf8_lossy(name)
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The header as it appears in the input, without the leading '@' or '>'.
+fn header(record: &fastq::Record) -> Vec<u8> {
+    let mut header = record.name().to_vec();
+    if !record.description().is_empty() {
+        header.push(b' ');
+        header.extend(record.description());
+    }
+    header
+}
+
+/// `--by-name` parts kept open at once. Parts beyond this are closed when least
+/// recently used and appended to when they get more reads, so many values do not
+/// run out of file descriptors. `--parts` keeps all its parts open instead, as it
+/// writes to every one of them in turn.
+const MAX_OPEN_PARTS: usize = 32;
+
+/// A part being written to.
+struct Part {
+    output: compression::Writer,
+    last_use: u64,
+}
+
+/// Opens parts on first use and writes reads to them.
+struct Parts {
+    out_dir: PathBuf,
+    prefix: String,
+    extension: String,
+    format: SequenceFormat,
+    max_open: Option<usize>,
+    open: HashMap<String, Part>,
+    created: HashSet<String>,
+    uses: u64,
+}
+
+impl Parts {
+    fn new(args: &SplitArgs, format: SequenceFormat) -> Self {
+        let mut extension = match format {
+            SequenceFormat::Fastq => "fq",
+            SequenceFormat::Fasta => "fa",
+        }
+        .to_string();
+        match args.compress {
+            Some(Compress::Gz) => extension.push_str(".gz"),
+            Some(Compress::Zst) => extension.push_str(".zst"),
+            None => {}
+        }
+
+        Self {
+            out_dir: args.out_dir.clone(),
+            prefix: args.prefix(),
+            extension,
+            format,
+            max_open: args.pattern.as_ref().map(|_| MAX_OPEN_PARTS),
+            open: HashMap::new(),
+            created: HashSet::new(),
+            uses: 0,
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.out_dir
+            .join(format!("{}.{name}.{}", self.prefix, self.extension))
+    }
+
+    /// Writes a read to the part called `name`, e.g. `part_001` or a barcode.
+    fn write(&mut self, name: &str, record: &fastq::Record) -> io::Result<()> {
+        self.uses += 1;
+
+        if !self.open.contains_key(name) {
+            if Some(self.open.len()) == self.max_open {
+                let (oldest, _) = self
+                    .open
+                    .iter()
+                    .min_by_key(|(_, part)| part.last_use)
+                    .unwrap();
+                self.close(&oldest.clone())?;
+            }
+
+            let dst = self.path(name);
+            let output = if self.created.contains(name) {
+                compression::append(&dst)
+            } else {
+                compression::create(Some(&dst))
+            }
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("failed to create {}: {e}", dst.display()))
+            })?;
+            self.created.insert(name.to_string());
+            self.open.insert(
+                name.to_string(),
+                Part {
+                    output,
+                    last_use: 0,
+                },
+            );
+        }
+
+        let part = self.open.get_mut(name).unwrap();
+        part.last_use = self.uses;
+        filter::Writer::new(&mut part.output, self.format).write_record(record)
+    }
+
+    /// Finishes a part, it is reopened for appending if it gets more reads.
+    fn close(&mut self, name: &str) -> io::Result<()> {
+        match self.open.remove(name) {
+            Some(part) => part.output.finish().map_err(|e| {
+                let dst = self.path(name);
+                io::Error::new(e.kind(), format!("failed to write {}: {e}", dst.display()))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let names: Vec<_> = self.open.keys().cloned().collect();
+        for name in names {
+            self.close(&name)?;
+        }
+        info!(
+            "wrote {} parts to {}",
+            self.created.len(),
+            self.out_dir.display()
+        );
+        Ok(())
+    }
+}
+
+fn part_name(i: usize) -> String {
+    format!("part_{:03}", i + 1)
+}
+
+pub fn split(args: &SplitArgs) -> Result<()> {
+    let mut reader = compression::open(&args.input)
+        .with_context(|| format!("failed to open {}", args.input.display()))?;
+    let format = match reader.fill_buf()?.first() {
+        Some(b'>') => SequenceFormat::Fasta,
+        Some(b'@') | None => SequenceFormat::Fastq,
+        Some(_) => bail!("{} is not a FASTQ or FASTA file", args.input.display()),
+    };
+
+    fs::create_dir_all(&args.out_dir)
+        .with_context(|| format!("failed to create {}", args.out_dir.display()))?;
+
+    let mut parts = Parts::new(args, format);
+    let (mut total, mut part, mut part_reads, mut part_bases) = (0, 0, 0, 0);
+
+    filter::for_each_record(reader, format, |record| {
+        let name = if let Some(pattern) = &args.pattern {
+            let header = header(&record);
+            match pattern.captures(&header) {
+                Some(captures) => {
+                    let m = captures.get(1).or_else(|| captures.get(0)).unwrap();
+                    sanitize(m.as_bytes())
+                }
+                None => "unmatched".to_string(),
+            }
+        } else {
+            let len = record.sequence().len();
+            if let Some(n) = args.parts {
+                part = total % n.get();
+            } else if matches!(args.reads, Some(k) if part_reads == k.get())
+                || matches!(args.bases, Some(b) if part_reads > 0 && part_bases + len > b.get())
+            {
+                // parts are written one after the other, so the full one can be closed
+                parts.close(&part_name(part))?;
+                part += 1;
+                part_reads = 0;
+                part_bases = 0;
+            }
+            part_reads += 1;
+            part_bases += len;
+            part_name(part)
+        };
+
+        total += 1;
+        parts.write(&name, &record)
+    })?;
+
+    parts.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize(b"ACGT-1"), "ACGT-1");
+        assert_eq!(sanitize(b"a/b c"), "a_b_c");
+    }
+}
@@ -0,0 +1,74 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+const SAM: &str = "@HD\tVN:1.6\tSO:unsorted
+@SQ\tSN:sq0\tLN:100
+p1\t99\tsq0\t1\t60\t4M\t=\t11\t14\tACGT\tABCD\tCB:Z:AAA
+p1\t147\tsq0\t11\t60\t4M\t=\t1\t-14\tAACC\tEFGH\tCB:Z:AAA
+p2\t137\tsq0\t21\t60\t4M\t=\t21\t0\tGGGG\tIIII
+p1\t2147\tsq0\t41\t60\t4M\t=\t1\t0\tACGT\tABCD
+p1\t355\tsq0\t51\t0\t4M\t=\t11\t0\tACGT\tABCD
+u1\t16\tsq0\t31\t60\t4M\t*\t0\t0\tAACG\tJKLM\tML:B:C,1,2\tHP:i:1
+";
+
+#[test]
+fn test_bam2fq_interleaved_with_tags() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["bam2fq", "-", "-T", "CB,ML,HP"]).write_stdin(SAM);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    // secondary and supplementary records are skipped, reverse reads are reverse-complemented
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@p1 CB:Z:AAA\nACGT\n+\nABCD\n\
+         @p1 CB:Z:AAA\nGGTT\n+\nHGFE\n\
+         @p2\nGGGG\n+\nIIII\n\
+         @u1 ML:B:C,1,2\tHP:i:1\nCGTT\n+\nMLKJ\n"
+    );
+}
+
+#[test]
+fn test_bam2fq_pairs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let sam = temp_dir.path().join("reads.sam");
+    let read1 = temp_dir.path().join("r1.fq");
+    let read2 = temp_dir.path().join("r2.fq.gz");
+    let singletons = temp_dir.path().join("singletons.fq");
+    fs::write(&sam, SAM).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("bam2fq")
+        .arg(&sam)
+        .arg("-1")
+        .arg(&read1)
+        .arg("-2")
+        .arg(&read2)
+        .arg("-s")
+        .arg(&singletons);
+    cmd.assert().success();
+
+    assert_eq!(fs::read_to_string(&read1).unwrap(), "@p1\nACGT\n+\nABCD\n");
+    assert_eq!(
+        fs::read_to_string(&singletons).unwrap(),
+        "@u1\nCGTT\n+\nMLKJ\n@p2\nGGGG\n+\nIIII\n"
+    );
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(&read2);
+    let output = cmd.output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), ">p1\nGGTT\n");
+}
+
+#[test]
+fn test_bam2fq_bam() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["bam2fq", "tests/data/reads.bam"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    // three primary records
+    let fastq = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(fastq.lines().count(), 12);
+}
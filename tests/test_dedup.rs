@@ -0,0 +1,48 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+const FASTA: &str = ">r1\nACGGT\n>r2\nacggt\n>r1\nTTTT\n>r3\nACCGT\n>r4\nACGGT\n";
+
+#[test]
+fn test_dedup_by_name() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["dedup", "-"]).write_stdin(FASTA);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1\nACGGT\n>r2\nacggt\n>r3\nACCGT\n>r4\nACGGT\n"
+    );
+}
+
+#[test]
+fn test_dedup_by_sequence_with_table() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let table = temp_dir.path().join("dups.tsv");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["dedup", "-", "--by-seq"]).write_stdin(FASTA);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1\nACGGT\n>r2\nacggt\n>r1\nTTTT\n>r3\nACCGT\n"
+    );
+
+    // ACCGT is the reverse complement of ACGGT
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["dedup", "-", "-s", "--ignore-case", "--revcomp", "-D"])
+        .arg(&table)
+        .write_stdin(FASTA);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1\nACGGT\n>r1\nTTTT\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&table).unwrap(),
+        "name\tduplicates\tduplicate_names\nr1\t3\tr2,r3,r4\n"
+    );
+}
@@ -31,3 +31,415 @@ fn test_extract_binary() {
     ]);
     cmd.assert().success();
 }
+
+fn record_names(stdout: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .filter(|line| !line.starts_with('@'))
+        .map(|line| line.split('\t').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_extract_region() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        "tests/data/reads.bam",
+        "-r",
+        "chr14:49586000-49587000",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 3);
+}
+
+#[test]
+fn test_extract_overlapping_regions() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        "tests/data/reads.bam",
+        "-r",
+        "chr7:90211800-90211900",
+        "-r",
+        "chr7:90211770-90211780",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 3);
+}
+
+#[test]
+fn test_extract_region_without_index_leaves_no_output() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.bam");
+    std::fs::copy("tests/data/reads.bam", &input).unwrap();
+    let output = temp_dir.path().join("out.sam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        input.to_str().unwrap(),
+        "-r",
+        "chr7:90211800-90211900",
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    cmd.assert().failure();
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_extract_region_with_read_ids() {
+    let bed = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(bed.path(), "chr7\t90211000\t90212000\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "-L",
+        bed.path().to_str().unwrap(),
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        record_names(&output.stdout),
+        [
+            "m64135_220622_211525/101318963/ccs",
+            "m64135_220622_211525/100861505/ccs"
+        ]
+    );
+}
+
+#[test]
+fn test_extract_output_format_from_extension() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("out.bam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "-o",
+        output.to_str().unwrap(),
+        "-l",
+        "1",
+    ]);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", output.to_str().unwrap()]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 4);
+}
+
+#[test]
+fn test_extract_threads_identical() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "--format", "bam"]);
+    let single = cmd.output().unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        "tests/data/reads.bam",
+        "--format",
+        "bam",
+        "-t",
+        "4",
+    ]);
+    let multi = cmd.output().unwrap();
+
+    assert!(single.status.success() && multi.status.success());
+    assert_eq!(single.stdout, multi.stdout);
+}
+
+#[test]
+fn test_extract_threads_reject_level() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("out.bam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        "tests/data/reads.bam",
+        "-l",
+        "1",
+        "-t",
+        "2",
+        "-o",
+    ])
+    .arg(&output);
+    let result = cmd.output().unwrap();
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("cannot be combined with more than one thread"));
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_extract_cram_requires_reference() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "--format", "cram"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_extract_invert() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "--invert",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        record_names(&output.stdout),
+        [
+            "m64135_220622_211525/121570614/ccs",
+            "m64135_220622_211525/121570614/ccs"
+        ]
+    );
+}
+
+#[test]
+fn test_extract_prefix_and_regex() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "m64135_220622_211525/1013",
+        "tests/data/reads.bam",
+        "--prefix",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 2);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        r"/1(00|21)\d+/ccs$",
+        "tests/data/reads.bam",
+        "--regex",
+        "--invert",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        record_names(&output.stdout),
+        [
+            "m64135_220622_211525/101318963/ccs",
+            "m64135_220622_211525/101318963/ccs"
+        ]
+    );
+}
+
+#[test]
+fn test_extract_flag_filters() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "--primary-only"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 3);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "-F", "0x900"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 3);
+}
+
+#[test]
+fn test_extract_tag_filters() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "",
+        "tests/data/reads.bam",
+        "--supplementary-only",
+        "--min-mapq",
+        "60",
+        "--tag",
+        "SA",
+        "--tag",
+        "NM:1",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        record_names(&output.stdout),
+        ["m64135_220622_211525/101318963/ccs"]
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-f 0x800 --min-mapq 60 --tag SA --tag NM:1"));
+}
+
+#[test]
+fn test_extract_to_fastq() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("reads.fq");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "--primary-only",
+        "--keep-soft-clips",
+        "-o",
+        output.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    let fastq = std::fs::read_to_string(output).unwrap();
+    let lines: Vec<&str> = fastq.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[0], "@m64135_220622_211525/101318963/ccs");
+    assert_eq!(lines[1].len(), 4041);
+    assert_eq!(lines[1].len(), lines[3].len());
+}
+
+#[test]
+fn test_extract_to_fastq_skips_secondary_and_supplementary() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "--to",
+        "fastq",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 8);
+}
+
+#[test]
+fn test_extract_to_fasta_without_soft_clips() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "m64135_220622_211525/100861505/ccs",
+        "tests/data/reads.bam",
+        "--to",
+        "fasta",
+        "-F",
+        "0x900",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let fasta = String::from_utf8(output.stdout).unwrap();
+    let sequence: String = fasta.lines().skip(1).collect();
+    assert!(fasta.starts_with(">m64135_220622_211525/100861505/ccs\n"));
+    assert_eq!(sequence.len(), 3739 - 249);
+}
+
+#[test]
+fn test_extract_missing_read_ids() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let missing = temp_dir.path().join("missing.txt");
+    let summary = temp_dir.path().join("summary.tsv");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "m64135_220622_211525/100861505/ccs,not_a_read",
+        "tests/data/reads.bam",
+        "--missing",
+        missing.to_str().unwrap(),
+        "--summary",
+        summary.to_str().unwrap(),
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(record_names(&output.stdout).len(), 2);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("requested 2 read IDs, found 1, missing 1, filtered out 0, wrote 2 records"));
+
+    assert_eq!(std::fs::read_to_string(missing).unwrap(), "not_a_read\n");
+    assert_eq!(
+        std::fs::read_to_string(summary).unwrap(),
+        "read_id\trecords\tfiltered\nm64135_220622_211525/100861505/ccs\t2\t0\nnot_a_read\t0\t0\n"
+    );
+}
+
+#[test]
+fn test_extract_filtered_read_ids_are_not_missing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let missing = temp_dir.path().join("missing.txt");
+    let summary = temp_dir.path().join("summary.tsv");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "m64135_220622_211525/100861505/ccs",
+        "tests/data/reads.bam",
+        "--min-mapq",
+        "255",
+        "--fail-on-missing",
+        "--missing",
+        missing.to_str().unwrap(),
+        "--summary",
+        summary.to_str().unwrap(),
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("requested 1 read IDs, found 1, missing 0, filtered out 1, wrote 0 records"));
+    assert_eq!(std::fs::read_to_string(missing).unwrap(), "");
+    assert_eq!(
+        std::fs::read_to_string(summary).unwrap(),
+        "read_id\trecords\tfiltered\nm64135_220622_211525/100861505/ccs\t0\t2\n"
+    );
+}
+
+#[test]
+fn test_extract_report_needs_exact_read_ids() {
+    for flag in ["--prefix", "--regex", "--invert"] {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args([
+            "extract",
+            "m64135",
+            "tests/data/reads.bam",
+            flag,
+            "--fail-on-missing",
+        ]);
+        cmd.assert().failure().code(2);
+    }
+}
+
+#[test]
+fn test_extract_fail_on_missing() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "not_a_read",
+        "tests/data/reads.bam",
+        "--fail-on-missing",
+    ]);
+    cmd.assert().failure().code(1);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "extract",
+        "tests/data/extract_1.txt",
+        "tests/data/reads.bam",
+        "--fail-on-missing",
+    ]);
+    cmd.assert().success();
+}
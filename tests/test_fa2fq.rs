@@ -16,3 +16,86 @@ fn test_fa2fq() {
 
     assert_eq!(ground_truth_sha256, output_sha256);
 }
+
+#[test]
+fn test_fa2fq_quality() {
+    for quality in ["I", "40"] {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["fa2fq", "tests/data/test_case1.fa", "--quality", quality]);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+
+        let fastq = String::from_utf8(output.stdout).unwrap();
+        let qualities = fastq.lines().nth(3).unwrap();
+        assert_eq!(qualities, "I".repeat(78));
+    }
+}
+
+#[test]
+fn test_fa2fq_qual_file_and_description() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fasta = temp_dir.path().join("reads.fa");
+    let qual = temp_dir.path().join("reads.qual");
+    fs::write(&fasta, ">r1 np=12 rq=0.99\nACGT\nAC\n>r2\nGG\n").unwrap();
+    fs::write(&qual, ">r1\n0 10 20\n30 40 93\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fa2fq").arg(&fasta).arg("--qual-from").arg(&qual);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@r1 np=12 rq=0.99\nACGTAC\n+\n!+5?I~\n@r2\nGG\n+\n@@\n"
+    );
+}
+
+#[test]
+fn test_fa2fq_qual_from_bam() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "-F", "0x900"])
+        .args(["--to", "fasta", "--keep-soft-clips"]);
+    let fasta = cmd.output().unwrap().stdout;
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", "", "tests/data/reads.bam", "-F", "0x900"])
+        .args(["--to", "fastq", "--keep-soft-clips"]);
+    let expected = cmd.output().unwrap().stdout;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let src = temp_dir.path().join("reads.fa");
+    fs::write(&src, fasta).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fa2fq")
+        .arg(&src)
+        .args(["--qual-from", "tests/data/reads.bam"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(
+        sha256::digest(output.stdout.as_slice()),
+        sha256::digest(expected.as_slice())
+    );
+}
+
+#[test]
+fn test_fa2fq_compressed_io() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let expected = fs::read("tests/data/test_case1.fq").unwrap();
+
+    for ext in ["gz", "bgz", "zst"] {
+        let output = temp_dir.path().join(format!("reads.fq.{ext}"));
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["fa2fq", "tests/data/test_case1.fa", "-o"])
+            .arg(&output);
+        cmd.assert().success();
+
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("fq2fa").arg(&output).args(["-o", "-"]);
+        let fasta = cmd.output().unwrap();
+        assert!(fasta.status.success());
+        assert_eq!(fasta.stdout, fs::read("tests/data/test_case1.fa").unwrap());
+        assert_ne!(fs::read(&output).unwrap(), expected);
+    }
+}
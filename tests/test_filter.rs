@@ -0,0 +1,62 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+const FASTQ: &str = "@r1 len=8\nACGTACGT\n+\nIIIIIIII\n@r2\nACGT\n+\nIIII\n@r3\nACGTACGT\n+\n++++++++\n@r4\nNNNNACGT\n+\nIIIIIIII\n";
+
+#[test]
+fn test_filter_length_quality_and_n() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fastq = temp_dir.path().join("reads.fq");
+    fs::write(&fastq, FASTQ).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("filter")
+        .arg(&fastq)
+        .args(["--min-length", "5", "--min-qual", "20", "--max-n", "0.1"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@r1 len=8\nACGTACGT\n+\nIIIIIIII\n"
+    );
+}
+
+#[test]
+fn test_filter_names_trim_and_fasta() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fastq = temp_dir.path().join("reads.fq");
+    let exclude = temp_dir.path().join("exclude.txt");
+    fs::write(&fastq, FASTQ).unwrap();
+    fs::write(&exclude, "r3\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("filter")
+        .arg(&fastq)
+        .args(["--include", "r1,r3,r4", "--exclude"])
+        .arg(&exclude)
+        .args(["--trim-head", "4", "--trim-tail", "1", "--to", "fasta"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1 len=8\nACG\n>r4\nACG\n"
+    );
+}
+
+#[test]
+fn test_filter_fasta_input() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["filter", "-", "--max-length", "4"])
+        .write_stdin(">r1\nACGTACGT\n>r2\nACGT\n");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), ">r2\nACGT\n");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["filter", "-", "--min-qual", "20"])
+        .write_stdin(">r1\nACGT\n");
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--min-qual needs FASTQ input"));
+}
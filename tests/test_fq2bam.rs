@@ -0,0 +1,86 @@
+use assert_cmd::cmd::Command;
+use noodles_sam::alignment::record::data::field::Tag;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+#[test]
+fn test_fq2bam_tags_and_read_group() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fastq = temp_dir.path().join("reads.fq");
+    let bam = temp_dir.path().join("reads.bam");
+    fs::write(
+        &fastq,
+        "@u1 CB:Z:AAA\tML:B:C,1,2 HP:i:1\nCGTT\n+\nMLKJ\n@u2 1:N:0:ACGT\nGG\n+\nII\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2bam")
+        .arg(&fastq)
+        .args(["-R", "run1", "-r", "SM:sample1", "-r", "PL:ONT", "-o"])
+        .arg(&bam);
+    cmd.assert().success();
+
+    let mut reader = noodles_bam::io::reader::Builder
+        .build_from_path(&bam)
+        .unwrap();
+    let header = reader.read_header().unwrap();
+    assert!(header.read_groups().contains_key(&b"run1"[..]));
+    assert!(header.programs().contains_key(&b"rboss"[..]));
+
+    let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].flags().is_unmapped());
+    assert!(records[1].data().get(&Tag::READ_GROUP).is_some());
+
+    // back to FASTQ with the same fields in the comment
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("bam2fq").arg(&bam).args(["-T", "CB,ML,HP,RG"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@u1 CB:Z:AAA\tML:B:C,1,2\tHP:i:1\tRG:Z:run1\nCGTT\n+\nMLKJ\n@u2 RG:Z:run1\nGG\n+\nII\n"
+    );
+}
+
+#[test]
+fn test_fq2bam_paired() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let read1 = temp_dir.path().join("r1.fq");
+    let read2 = temp_dir.path().join("r2.fq");
+    fs::write(&read1, "@p1/1\nACGT\n+\nABCD\n@p2/1\nAA\n+\nII\n").unwrap();
+    fs::write(&read2, "@p1/2\nGGTT\n+\nHGFE\n@p2/2\nCC\n+\nII\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2bam").arg(&read1).arg(&read2);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let mut reader = noodles_bam::io::Reader::new(&output.stdout[..]);
+    reader.read_header().unwrap();
+    let flags: Vec<_> = reader
+        .records()
+        .map(|record| {
+            let record = record.unwrap();
+            (
+                record.name().unwrap().as_bytes().to_vec(),
+                u16::from(record.flags()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        flags,
+        [
+            (b"p1".to_vec(), 77),
+            (b"p1".to_vec(), 141),
+            (b"p2".to_vec(), 77),
+            (b"p2".to_vec(), 141)
+        ]
+    );
+
+    fs::write(&read2, "@p1/2\nGGTT\n+\nHGFE\n").unwrap();
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2bam").arg(&read1).arg(&read2);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+}
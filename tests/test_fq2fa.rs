@@ -16,3 +16,76 @@ fn test_fq2fa() {
 
     assert_eq!(ground_truth_sha256, output_sha256);
 }
+
+#[test]
+fn test_fq2fa_description_and_line_width() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fastq = temp_dir.path().join("reads.fq");
+    fs::write(
+        &fastq,
+        "@r1 np=12 rq=0.99\nACGTACGTAC\n+\nIIIIIIIIII\n@r2 1:N:0:ACGT\nAC\n+\nII\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(&fastq).args(["--line-width", "4"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1 np=12 rq=0.99\nACGT\nACGT\nAC\n>r2 1:N:0:ACGT\nAC\n"
+    );
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(&fastq).args(["-w", "0"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1 np=12 rq=0.99\nACGTACGTAC\n>r2 1:N:0:ACGT\nAC\n"
+    );
+}
+
+#[test]
+fn test_fq2fa_compressed_io() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let expected = fs::read("tests/data/test_case1.fa").unwrap();
+
+    for ext in ["gz", "zst"] {
+        let output = temp_dir.path().join(format!("reads.fa.{ext}"));
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["fq2fa", "tests/data/test_case1.fq", "-o"])
+            .arg(&output);
+        cmd.assert().success();
+        assert_ne!(fs::read(&output).unwrap(), expected);
+
+        // read the compressed file back from stdin
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["fa2fq", "-"])
+            .write_stdin(fs::read(&output).unwrap());
+        let fastq = cmd.output().unwrap();
+        assert!(fastq.status.success());
+        assert_eq!(fastq.stdout, fs::read("tests/data/test_case1.fq").unwrap());
+    }
+}
+
+#[test]
+fn test_fq2fa_gzip_input() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.fq.gz");
+
+    // plain gzip, as written by `gzip`, not bgzip
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(&fs::read("tests/data/test_case1.fq").unwrap())
+        .unwrap();
+    fs::write(&input, encoder.finish().unwrap()).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(&input);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, fs::read("tests/data/test_case1.fa").unwrap());
+}
@@ -0,0 +1,224 @@
+use assert_cmd::cmd::Command;
+use noodles_csi::BinningIndex;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn test_index_threads_identical() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["index", "tests/data/reads.bam", "-o", "-"]);
+    let single = cmd.output().unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["index", "tests/data/reads.bam", "-o", "-", "-t", "4"]);
+    let multi = cmd.output().unwrap();
+
+    assert!(single.status.success() && multi.status.success());
+    assert!(!single.stdout.is_empty());
+    assert_eq!(
+        sha256::digest(single.stdout.as_slice()),
+        sha256::digest(multi.stdout.as_slice())
+    );
+}
+
+#[test]
+fn test_index_next_to_input() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let bam = temp_dir.path().join("reads.bam");
+    std::fs::copy("tests/data/reads.bam", &bam).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&bam);
+    let output = cmd.output().unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let bai = std::fs::read(temp_dir.path().join("reads.bam.bai")).unwrap();
+    assert_eq!(&bai[..4], b"BAI\x01");
+}
+
+#[test]
+fn test_index_csi() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let bam = temp_dir.path().join("reads.bam");
+    std::fs::copy("tests/data/reads.bam", &bam).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index")
+        .arg(&bam)
+        .args(["--csi", "--min-shift", "12", "--depth", "6"]);
+    assert!(cmd.output().unwrap().status.success());
+
+    let csi = temp_dir.path().join("reads.bam.csi");
+    assert!(csi.exists());
+    assert!(!temp_dir.path().join("reads.bam.bai").exists());
+
+    // the CSI index can be used for region queries
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", ""]).arg(&bam).args(["-r", "chr14"]);
+    let output = cmd.output().unwrap();
+
+    assert!(output.status.success());
+    let records = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(records.lines().filter(|l| !l.starts_with('@')).count(), 3);
+}
+
+#[test]
+fn test_index_csi_options_require_csi() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["index", "tests/data/reads.bam", "--min-shift", "12"]);
+    cmd.assert().failure();
+}
+
+fn bgzip(src: &[u8], dst: &std::path::Path) {
+    use std::io::Write;
+
+    let mut writer = noodles_bgzf::Writer::new(std::fs::File::create(dst).unwrap());
+    writer.write_all(src).unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn test_index_fasta() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fa = temp_dir.path().join("test_case1.fa");
+    std::fs::copy("tests/data/test_case1.fa", &fa).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&fa);
+    assert!(cmd.output().unwrap().status.success());
+
+    let fai = noodles_fasta::fai::read(temp_dir.path().join("test_case1.fa.fai")).unwrap();
+    assert_eq!(fai, noodles_fasta::index(&fa).unwrap());
+}
+
+#[test]
+fn test_index_bgzipped_fasta() {
+    // several BGZF blocks, so the GZI index has entries
+    let mut fasta = String::new();
+    for (i, base) in ["A", "C", "G"].iter().enumerate() {
+        fasta.push_str(&format!(">sq{i}\n"));
+        for _ in 0..2000 {
+            fasta.push_str(&base.repeat(60));
+            fasta.push('\n');
+        }
+    }
+    fasta.push_str(">sq3 last\nACGTN\n");
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fa = temp_dir.path().join("ref.fa");
+    let fa_gz = temp_dir.path().join("ref.fa.gz");
+    std::fs::write(&fa, &fasta).unwrap();
+    bgzip(fasta.as_bytes(), &fa_gz);
+
+    for src in [&fa, &fa_gz] {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("index").arg(src);
+        assert!(cmd.output().unwrap().status.success());
+    }
+
+    let fai = std::fs::read(temp_dir.path().join("ref.fa.fai")).unwrap();
+    let fai_gz = std::fs::read(temp_dir.path().join("ref.fa.gz.fai")).unwrap();
+    assert_eq!(fai, fai_gz);
+
+    let gzi = noodles_bgzf::gzi::read(temp_dir.path().join("ref.fa.gz.gzi")).unwrap();
+    assert!(gzi.len() > 1);
+
+    let mut reader = noodles_fasta::indexed_reader::Builder::default()
+        .build_from_path(&fa_gz)
+        .unwrap();
+    let record = reader.query(&"sq2:119990-120000".parse().unwrap()).unwrap();
+    assert_eq!(record.sequence().as_ref(), b"GGGGGGGGGGG");
+    let record = reader.query(&"sq3".parse().unwrap()).unwrap();
+    assert_eq!(record.sequence().as_ref(), b"ACGTN");
+}
+
+#[test]
+fn test_index_fastq() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fq = temp_dir.path().join("test_case1.fq");
+    std::fs::copy("tests/data/test_case1.fq", &fq).unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&fq).args(["-o", "-"]);
+    let output = cmd.output().unwrap();
+
+    assert!(output.status.success());
+    let fai = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(fai, "sequence1\t78\t11\t78\t79\t92\n");
+}
+
+const VCF: &str = "##fileformat=VCFv4.3
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO
+chr1\t100\t.\tA\tT\t.\t.\t.
+chr1\t200\t.\tACGT\tA\t.\t.\t.
+chr2\t50\t.\tN\t<DEL>\t.\t.\tSVTYPE=DEL;END=900
+";
+
+#[test]
+fn test_index_vcf() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let vcf = temp_dir.path().join("sample.vcf.gz");
+    bgzip(VCF.as_bytes(), &vcf);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&vcf);
+    assert!(cmd.output().unwrap().status.success());
+
+    let index = noodles_tabix::read(temp_dir.path().join("sample.vcf.gz.tbi")).unwrap();
+    let names: Vec<_> = index
+        .header()
+        .unwrap()
+        .reference_sequence_names()
+        .iter()
+        .collect();
+    assert_eq!(names, ["chr1", "chr2"]);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&vcf).arg("--csi");
+    assert!(cmd.output().unwrap().status.success());
+
+    let index = noodles_csi::read(temp_dir.path().join("sample.vcf.gz.csi")).unwrap();
+    assert_eq!(index.header().unwrap().reference_sequence_names().len(), 2);
+}
+
+#[test]
+fn test_index_sam_and_bed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let sam = temp_dir.path().join("extract_1.sam.gz");
+    bgzip(&std::fs::read("tests/data/extract_1.sam").unwrap(), &sam);
+
+    let bed = temp_dir.path().join("regions.bed.gz");
+    bgzip(
+        b"track name=test\nchr7\t0\t100\nchr7\t50\t60\nchr14\t10\t20\n",
+        &bed,
+    );
+
+    for (src, dst) in [(&sam, "extract_1.sam.gz.tbi"), (&bed, "regions.bed.gz.tbi")] {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("index").arg(src);
+        assert!(cmd.output().unwrap().status.success());
+        assert!(temp_dir.path().join(dst).exists());
+    }
+}
+
+#[test]
+fn test_index_unsorted_or_uncompressed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let bed = temp_dir.path().join("unsorted.bed.gz");
+    bgzip(b"chr1\t10\t20\nchr2\t0\t5\nchr1\t30\t40\n", &bed);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&bed);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not sorted"));
+    assert!(!temp_dir.path().join("unsorted.bed.gz.tbi").exists());
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["index", "tests/data/lncap_test.vcf"]);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bgzip"));
+}
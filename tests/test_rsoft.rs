@@ -40,3 +40,409 @@ fn test_rsoft_all() {
     assert!(temp_dir.path().join("extract_1.bam").is_symlink());
     assert!(temp_dir.path().join("reads.bam").is_symlink());
 }
+
+/// Two runs that both have a sample.bam.
+fn runs() -> tempfile::TempDir {
+    let temp_dir = tempdir().unwrap();
+    for run in ["run1", "run2"] {
+        let dir = temp_dir.path().join("source").join(run);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("sample.bam"), run).unwrap();
+    }
+    std::fs::write(temp_dir.path().join("source/run1/notes.txt"), "").unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_rsoft_dry_run_and_collisions() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("links");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("rsoft").arg(&source).arg("-t").arg(&target).args([
+        "-s",
+        "bam",
+        "--dry-run",
+        "--relative",
+        "--on-collision",
+        "suffix",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("sample_run1.bam -> ../source/run1/sample.bam"));
+    assert!(stdout.contains("sample_run2.bam -> ../source/run2/sample.bam"));
+    assert!(!target.exists());
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("rsoft").arg(&source).arg("-t").arg(&target).args([
+        "-s",
+        "bam",
+        "--on-collision",
+        "error",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 files are named sample.bam"));
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("rsoft")
+        .arg(&source)
+        .arg("-t")
+        .arg(&target)
+        .args(["-s", "bam", "--relative"]);
+    cmd.assert().success();
+    let link = target.join("sample.bam");
+    assert_eq!(
+        std::fs::read_link(&link).unwrap(),
+        std::path::Path::new("../source/run1/sample.bam")
+    );
+    assert_eq!(std::fs::read_to_string(&link).unwrap(), "run1");
+}
+
+#[test]
+fn test_rsoft_mirror() {
+    let temp_dir = runs();
+    let target = temp_dir.path().join("links");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("rsoft")
+        .arg(temp_dir.path().join("source"))
+        .arg("-t")
+        .arg(&target)
+        .args(["--on-collision", "mirror"]);
+    cmd.assert().success();
+
+    assert_eq!(
+        std::fs::read_to_string(target.join("run2/sample.bam")).unwrap(),
+        "run2"
+    );
+    assert!(target.join("run1/sample.bam").is_symlink());
+    assert!(target.join("run1/notes.txt").is_symlink());
+}
+
+#[test]
+fn test_rsoft_patterns_and_depth() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("delivery");
+    for dir in ["fc1/pass", "fc1/fail", "fc2/pass/barcode01"] {
+        std::fs::create_dir_all(source.join(dir)).unwrap();
+    }
+    for file in [
+        "fc1/pass/a.fastq.gz",
+        "fc1/fail/b.fastq.gz",
+        "fc2/pass/c.fastq.gz",
+        "fc2/pass/c.bam",
+        "fc2/pass/barcode01/d.fastq.gz",
+        "top.fastq.gz",
+    ] {
+        std::fs::write(source.join(file), "").unwrap();
+    }
+
+    let linked = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft")
+            .arg(&source)
+            .args(["-t", "links", "--dry-run", "-c", "mirror"])
+            .args(args);
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        let mut names: Vec<_> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(" -> ").next().unwrap().to_string())
+            .map(|link| link.split("links/").last().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(
+        linked(&["-g", "*/pass/*"]),
+        [
+            "fc1/pass/a.fastq.gz",
+            "fc2/pass/c.bam",
+            "fc2/pass/c.fastq.gz"
+        ]
+    );
+    assert_eq!(
+        linked(&["-g", "**/*.fastq.gz", "-x", "**/fail", "--min-depth", "3"]),
+        [
+            "fc1/pass/a.fastq.gz",
+            "fc2/pass/barcode01/d.fastq.gz",
+            "fc2/pass/c.fastq.gz"
+        ]
+    );
+    assert_eq!(
+        linked(&["-e", r"^fc\d/pass/[^/]+$", "-s", "bam"]),
+        ["fc2/pass/c.bam"]
+    );
+    assert_eq!(linked(&["--max-depth", "1"]), ["top.fastq.gz"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rsoft_follow_links() {
+    let temp_dir = tempdir().unwrap();
+    let source = temp_dir.path().join("source");
+    let other = temp_dir.path().join("other");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&other).unwrap();
+    std::fs::write(other.join("a.bam"), "").unwrap();
+    std::os::unix::fs::symlink(&other, source.join("run1")).unwrap();
+
+    for (follow, expected) in [(false, false), (true, true)] {
+        let target = temp_dir.path().join(format!("links_{follow}"));
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft").arg(&source).arg("-t").arg(&target);
+        if follow {
+            cmd.arg("--follow-links");
+        }
+        cmd.assert().success();
+        assert_eq!(target.join("a.bam").is_symlink(), expected);
+    }
+}
+
+#[test]
+fn test_rsoft_manifest_prune_and_verify() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("links");
+    let rsoft = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft")
+            .arg(&source)
+            .arg("-t")
+            .arg(&target)
+            .args(args);
+        cmd.output().unwrap()
+    };
+
+    assert!(rsoft(&["-c", "suffix", "--checksum"]).status.success());
+    let manifest = std::fs::read_to_string(target.join(".rsoft_manifest.tsv")).unwrap();
+    let lines: Vec<_> = manifest.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "link\tsource\tsize\tchecksum\tmode\tmtime");
+    assert!(lines[2].starts_with("sample_run1.bam\t"));
+    assert!(lines[2].contains("/source/run1/sample.bam\t4\t"));
+
+    let verify = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["rsoft", "--verify", "-t"])
+            .arg(&target)
+            .args(args);
+        cmd.output().unwrap()
+    };
+    assert!(verify(&[]).status.success());
+
+    // a changed file, a removed file and a replaced link
+    std::fs::write(source.join("run1/sample.bam"), "RUN1").unwrap();
+    std::fs::remove_file(source.join("run2/sample.bam")).unwrap();
+    std::fs::remove_file(target.join("notes.txt")).unwrap();
+    std::fs::write(target.join("notes.txt"), "mine").unwrap();
+
+    let output = verify(&[]);
+    assert!(!output.status.success());
+    let mut report: Vec<_> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split('\t').take(2).collect::<Vec<_>>().join(" "))
+        .collect();
+    report.sort();
+    assert_eq!(
+        report,
+        [
+            "broken sample_run2.bam",
+            "changed sample_run1.bam",
+            "retargeted notes.txt"
+        ]
+    );
+
+    // only bam files match now, and the remaining sample.bam no longer collides;
+    // the replaced notes.txt is kept
+    assert!(rsoft(&[
+        "-s",
+        "bam",
+        "-c",
+        "suffix",
+        "--prune",
+        "--manifest-format",
+        "json"
+    ])
+    .status
+    .success());
+    assert!(!target.join("sample_run1.bam").is_symlink());
+    assert!(!target.join("sample_run2.bam").is_symlink());
+    assert!(target.join("sample.bam").is_symlink());
+    assert_eq!(
+        std::fs::read_to_string(target.join("notes.txt")).unwrap(),
+        "mine"
+    );
+    assert!(!target.join(".rsoft_manifest.tsv").exists());
+    let manifest = std::fs::read_to_string(target.join(".rsoft_manifest.json")).unwrap();
+    assert!(manifest.contains("\"link\": \"sample.bam\""));
+    assert!(!manifest.contains("notes.txt"));
+    assert!(verify(&[]).status.success());
+}
+
+#[test]
+fn test_rsoft_checksum_only_changed_files() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+    let target = temp_dir.path().join("links");
+    let manifest = target.join(".rsoft_manifest.tsv");
+    let rsoft = || {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft").arg(&source).arg("-t").arg(&target).args([
+            "-s",
+            "bam",
+            "-c",
+            "mirror",
+            "--checksum",
+        ]);
+        cmd.assert().success();
+    };
+    let checksum = |link: &str| {
+        let manifest = std::fs::read_to_string(&manifest).unwrap();
+        let line = manifest
+            .lines()
+            .find(|line| line.starts_with(&format!("{link}\t")))
+            .unwrap();
+        line.split('\t').nth(3).unwrap().to_string()
+    };
+    rsoft();
+    let run1 = checksum("run1/sample.bam");
+    let run2 = checksum("run2/sample.bam");
+
+    // a recorded checksum is taken as is while the file keeps its size and modification time
+    let recorded = std::fs::read_to_string(&manifest).unwrap();
+    std::fs::write(&manifest, recorded.replace(&run1, "0000000000000001")).unwrap();
+    rsoft();
+    assert_eq!(checksum("run1/sample.bam"), "0000000000000001");
+
+    // rewriting the file gives it a new modification time, so it is read again
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    std::fs::write(source.join("run1/sample.bam"), "run1").unwrap();
+    rsoft();
+    assert_eq!(checksum("run1/sample.bam"), run1);
+    assert_eq!(checksum("run2/sample.bam"), run2);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["rsoft", "--verify", "-t"]).arg(&target);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_rsoft_modes() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+
+    for mode in ["hardlink", "copy", "reflink"] {
+        let target = temp_dir.path().join(mode);
+        let rsoft = || {
+            let mut cmd = Command::cargo_bin("rboss").unwrap();
+            cmd.arg("rsoft").arg(&source).arg("-t").arg(&target).args([
+                "-s",
+                "bam",
+                "-c",
+                "mirror",
+                "--checksum",
+                "--mode",
+                mode,
+            ]);
+            cmd.assert().success();
+        };
+        rsoft();
+
+        let link = target.join("run1/sample.bam");
+        assert!(!link.is_symlink());
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "run1");
+        let manifest = std::fs::read_to_string(target.join(".rsoft_manifest.tsv")).unwrap();
+        // reflinks fall back to copies on filesystems without them
+        assert!(manifest.lines().skip(1).all(|line| {
+            let recorded = line.split('\t').nth(4).unwrap();
+            recorded == mode || (mode == "reflink" && recorded == "copy")
+        }));
+
+        // a hard link shares the change, a copy keeps the old content, both are reported
+        std::fs::write(source.join("run2/sample.bam"), "RUN2").unwrap();
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["rsoft", "--verify", "-t"]).arg(&target);
+        let output = cmd.output().unwrap();
+        let report = String::from_utf8(output.stdout).unwrap();
+        if mode == "hardlink" {
+            assert_eq!(
+                std::fs::read_to_string(target.join("run2/sample.bam")).unwrap(),
+                "RUN2"
+            );
+        } else {
+            assert_eq!(
+                std::fs::read_to_string(target.join("run2/sample.bam")).unwrap(),
+                "run2"
+            );
+        }
+        assert!(report.starts_with("changed\trun2/sample.bam"));
+        assert!(!output.status.success());
+
+        // re-running keeps the copies that are up to date, and the manifest verifies again
+        std::fs::write(source.join("run2/sample.bam"), "run2").unwrap();
+        rsoft();
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args(["rsoft", "--verify", "-t"]).arg(&target);
+        cmd.assert().success();
+    }
+}
+
+#[test]
+fn test_rsoft_relative_needs_symlinks() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+
+    for mode in ["hardlink", "copy", "reflink"] {
+        let target = temp_dir.path().join(mode);
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft")
+            .arg(&source)
+            .arg("-t")
+            .arg(&target)
+            .args(["-r", "--mode", mode]);
+        let output = cmd.output().unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains(&format!("'--relative' cannot be used with '--mode {mode}'")));
+        assert!(!target.exists());
+    }
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("rsoft")
+        .arg(&source)
+        .arg("-t")
+        .arg(temp_dir.path().join("symlink"))
+        .args(["-r", "--mode", "symlink"]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_rsoft_chained_manifests() {
+    let temp_dir = runs();
+    let source = temp_dir.path().join("source");
+    let first = temp_dir.path().join("first");
+    let second = temp_dir.path().join("second");
+
+    for (source, target) in [(&source, &first), (&first, &second)] {
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.arg("rsoft").arg(source).arg("-t").arg(target);
+        cmd.assert().success();
+    }
+
+    // the manifest of the first run is neither linked nor overwritten by the second
+    let manifest = std::fs::read_to_string(first.join(".rsoft_manifest.tsv")).unwrap();
+    assert!(manifest.contains("/source/"));
+    let manifest = second.join(".rsoft_manifest.tsv");
+    assert!(manifest.symlink_metadata().unwrap().is_file());
+    assert!(std::fs::read_to_string(manifest)
+        .unwrap()
+        .contains("/first/"));
+}
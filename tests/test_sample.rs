@@ -0,0 +1,129 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+fn fastq(n: usize) -> String {
+    (0..n).map(|i| format!("@r{i}\nACGT\n+\nIIII\n")).collect()
+}
+
+fn sample(input: &Path, args: &[&str]) -> Vec<u8> {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("sample").arg(input).args(args);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    output.stdout
+}
+
+fn names(fastq: &[u8]) -> Vec<usize> {
+    String::from_utf8_lossy(fastq)
+        .lines()
+        .step_by(4)
+        .map(|line| line[2..].parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_sample_fastq_count() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.fq");
+    fs::write(&input, fastq(100)).unwrap();
+
+    let first = sample(&input, &["-n", "10", "-s", "7"]);
+    let second = sample(&input, &["-n", "10", "-s", "7"]);
+    assert_eq!(first, second);
+
+    // written in input order
+    let sampled = names(&first);
+    assert_eq!(sampled.len(), 10);
+    assert!(sampled.windows(2).all(|w| w[0] < w[1]));
+
+    assert_ne!(first, sample(&input, &["-n", "10", "-s", "8"]));
+    assert_eq!(names(&sample(&input, &["-n", "200"])).len(), 100);
+}
+
+#[test]
+fn test_sample_fastq_fraction() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.fq");
+    fs::write(&input, fastq(1000)).unwrap();
+
+    let sampled = sample(&input, &["-f", "0.5"]);
+    assert_eq!(
+        sampled,
+        sample(&input, &["--fraction", "0.5", "--seed", "11"])
+    );
+    assert!((400..600).contains(&names(&sampled).len()));
+
+    assert!(sample(&input, &["-f", "0"]).is_empty());
+    assert_eq!(names(&sample(&input, &["-f", "1"])).len(), 1000);
+}
+
+#[test]
+fn test_sample_bam_keeps_templates() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("sampled.bam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sample", "tests/data/reads.bam", "-n", "2", "-o"])
+        .arg(&output);
+    cmd.assert().success();
+
+    let mut reader = noodles_bam::io::reader::Builder
+        .build_from_path(&output)
+        .unwrap();
+    reader.read_header().unwrap();
+
+    let mut counts = HashMap::new();
+    for result in reader.records() {
+        let record = result.unwrap();
+        let name = record.name().unwrap().as_bytes().to_vec();
+        *counts.entry(name).or_insert(0) += 1;
+    }
+
+    // reads.bam has three templates of a primary and a supplementary record each
+    assert_eq!(counts.len(), 2);
+    assert!(counts.values().all(|&count| count == 2));
+}
+
+#[test]
+fn test_sample_bam_finishes_output() {
+    const BGZF_EOF: [u8; 28] = [
+        0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02,
+        0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    for threads in ["1", "2"] {
+        let output = temp_dir.path().join(format!("sampled.{threads}.bam"));
+        let mut cmd = Command::cargo_bin("rboss").unwrap();
+        cmd.args([
+            "sample",
+            "tests/data/reads.bam",
+            "-f",
+            "1",
+            "-t",
+            threads,
+            "-o",
+        ])
+        .arg(&output);
+        cmd.assert().success();
+        assert!(std::fs::read(&output).unwrap().ends_with(&BGZF_EOF));
+
+        // write errors at the end of the output are not lost
+        if cfg!(target_os = "linux") {
+            let mut cmd = Command::cargo_bin("rboss").unwrap();
+            cmd.args(["sample", "tests/data/reads.bam", "-f", "1", "-t", threads])
+                .args(["-o", "/dev/full"]);
+            cmd.assert().failure();
+        }
+    }
+}
+
+#[test]
+fn test_sample_requires_amount() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sample", "tests/data/test_case1.fq"]);
+    cmd.assert().failure();
+}
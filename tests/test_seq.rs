@@ -0,0 +1,74 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+#[test]
+fn test_seq_revcomp_and_case() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["seq", "-", "--revcomp", "--upper"])
+        .write_stdin("@r1 desc\nAACgtN\n+\nABCDEF\n");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@r1 desc\nNACGTT\n+\nFEDCBA\n"
+    );
+}
+
+#[test]
+fn test_seq_revcomp_keeps_case_and_iupac() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["seq", "-", "--revcomp"])
+        .write_stdin(">r1\nACGTNacgtnRYKM-\n");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1\n-KMRYnacgtNACGT\n"
+    );
+}
+
+#[test]
+fn test_seq_translate_frames() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["seq", "-", "--translate", "--frames", "1,-1"])
+        .write_stdin("@r1\nATGGCCTAAG\n+\nIIIIIIIIII\n");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1_frame=1\nMA*\n>r1_frame=-1\nLRP\n"
+    );
+}
+
+#[test]
+fn test_seq_bed_regions() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fasta = temp_dir.path().join("ref.fa");
+    let bed = temp_dir.path().join("regions.bed");
+    fs::write(&fasta, ">chr1\nAACCGGTT\nACGTACGT\n>chr2\nGGGGAAAA\n").unwrap();
+    fs::write(
+        &bed,
+        "track name=test\nchr1\t6\t10\nchr2\t2\t6\tgene1\t0\t-\n",
+    )
+    .unwrap();
+
+    // the FASTA file is not indexed yet
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("seq").arg(&fasta).arg("--bed").arg(&bed);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rboss index"));
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&fasta).assert().success();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("seq").arg(&fasta).arg("--bed").arg(&bed);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">chr1:7-10\nTTAC\n>gene1\nTTCC\n"
+    );
+}
@@ -0,0 +1,75 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+
+#[test]
+fn test_seqstats() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fastq = temp_dir.path().join("reads.fq");
+    fs::write(
+        &fastq,
+        "@r1\nACGTACGT\n+\nIIIIIIII\n@r2\nGGCC\n+\n++++\n@r3\nAT\n+\n55\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("seqstats")
+        .arg(&fastq)
+        .arg("tests/data/test_case1.fa");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("file\tformat\tnum_seqs\tsum_len"));
+    // 14 bases, I is Q40, + is Q10 and 5 is Q20
+    assert_eq!(
+        lines[1],
+        format!(
+            "{}\tFASTQ\t3\t14\t2\t4.67\t8\t8\t2\t57.14\t28.57\t71.43\t57.14",
+            fastq.display()
+        )
+    );
+    assert_eq!(
+        lines[2],
+        "tests/data/test_case1.fa\tFASTA\t1\t78\t78\t78.00\t78\t78\t78\t48.72\t-\t-\t-"
+    );
+}
+
+#[test]
+fn test_seqstats_json() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args([
+        "stats",
+        "--json",
+        "-t",
+        "2",
+        "tests/data/reads.bam",
+        "tests/data/test_case1.fq",
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let stats = stats.as_array().unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0]["file"], "tests/data/reads.bam");
+    assert_eq!(stats[0]["format"], "BAM");
+    assert_eq!(stats[1]["format"], "FASTQ");
+    assert_eq!(stats[1]["num_seqs"], 1);
+    assert_eq!(stats[1]["sum_len"], 78);
+}
+
+#[test]
+fn test_seqstats_per_read() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["seqstats", "--per-read", "-"])
+        .write_stdin(">r1 desc\nGGCCAT\n>r2\nAAAA\n");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "file\tname\tlength\tgc\tmean_qual\n-\tr1\t6\t66.67\t-\n-\tr2\t4\t0.00\t-\n"
+    );
+}
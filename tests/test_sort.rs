@@ -0,0 +1,140 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+
+/// The records of a BAM as SAM lines, without the header.
+fn records<P: AsRef<std::path::Path>>(src: P) -> Vec<String> {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", ""]).arg(src.as_ref());
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with('@'))
+        .map(String::from)
+        .collect()
+}
+
+fn header_line<P: AsRef<std::path::Path>>(src: P) -> String {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["extract", ""]).arg(src.as_ref());
+    let output = cmd.output().unwrap();
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .find(|line| line.starts_with("@HD"))
+        .unwrap()
+        .to_string()
+}
+
+fn names(records: &[String]) -> Vec<&str> {
+    records
+        .iter()
+        .map(|record| record.split('\t').next().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_sort_by_name_and_back() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let by_name = temp_dir.path().join("name.bam");
+    let by_coordinate = temp_dir.path().join("coordinate.bam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sort", "-n", "tests/data/reads.bam", "-o"])
+        .arg(&by_name);
+    assert!(cmd.output().unwrap().status.success());
+
+    let sorted = records(&by_name);
+    let mut expected = names(&sorted);
+    expected.sort();
+    assert_eq!(names(&sorted), expected);
+    assert!(header_line(&by_name).contains("SO:queryname\tSS:queryname:lexicographical"));
+
+    // a tiny memory limit spills every record to a temporary file
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("sort")
+        .arg(&by_name)
+        .args(["-m", "1K", "-T"])
+        .arg(temp_dir.path())
+        .arg("-o")
+        .arg(&by_coordinate);
+    assert!(cmd.output().unwrap().status.success());
+
+    assert_eq!(records(&by_coordinate), records("tests/data/reads.bam"));
+    assert!(header_line(&by_coordinate).contains("SO:coordinate"));
+}
+
+#[test]
+fn test_sort_by_tag() {
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sort", "--tag", "NM", "tests/data/reads.bam"]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dst = temp_dir.path().join("tag.bam");
+    std::fs::write(&dst, output.stdout).unwrap();
+
+    let nm: Vec<i64> = records(&dst)
+        .iter()
+        .map(|record| {
+            record
+                .split('\t')
+                .find_map(|field| field.strip_prefix("NM:i:"))
+                .unwrap()
+                .parse()
+                .unwrap()
+        })
+        .collect();
+    let mut expected = nm.clone();
+    expected.sort();
+    assert_eq!(nm, expected);
+    assert!(header_line(&dst).contains("SS:unknown:tag:NM:coordinate"));
+}
+
+#[test]
+fn test_index_checks_record_order() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let unsorted = temp_dir.path().join("unsorted.bam");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sort", "-n", "tests/data/reads.bam", "-o"])
+        .arg(&unsorted);
+    assert!(cmd.output().unwrap().status.success());
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&unsorted);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not coordinate-sorted"));
+    assert!(!temp_dir.path().join("unsorted.bam.bai").exists());
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("index").arg(&unsorted).arg("--sort");
+    assert!(cmd.output().unwrap().status.success());
+
+    let sorted = temp_dir.path().join("unsorted.sorted.bam");
+    assert!(temp_dir.path().join("unsorted.sorted.bam.bai").exists());
+    assert_eq!(records(&sorted), records("tests/data/reads.bam"));
+}
+
+#[test]
+fn test_sort_threads_reject_level() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("sorted.bam");
+    std::fs::write(&output, "kept").unwrap();
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.args(["sort", "tests/data/reads.bam", "-l", "1", "-t", "2", "-o"])
+        .arg(&output);
+    let result = cmd.output().unwrap();
+    assert!(!result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("cannot be combined with more than one thread"));
+    // the existing file is left alone
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), "kept");
+}
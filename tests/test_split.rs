@@ -0,0 +1,210 @@
+use assert_cmd::cmd::Command;
+use pretty_assertions::assert_eq;
+use std::fs;
+use std::path::Path;
+
+fn read_names(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .step_by(4)
+        .map(|line| line[1..].to_string())
+        .collect()
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+fn write_fastq(dir: &Path) -> std::path::PathBuf {
+    let input = dir.join("reads.fastq");
+    let fastq: String = [
+        ("r1 BC=AAA", "ACGTACGT"),
+        ("r2 BC=CCC", "ACGT"),
+        ("r3 BC=AAA", "ACGTAC"),
+        ("r4", "AC"),
+        ("r5 BC=CCC", "ACGTACGTAC"),
+    ]
+    .iter()
+    .map(|(header, sequence)| format!("@{header}\n{sequence}\n+\n{}\n", "I".repeat(sequence.len())))
+    .collect();
+    fs::write(&input, fastq).unwrap();
+    input
+}
+
+#[test]
+fn test_split_parts_and_reads() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = write_fastq(temp_dir.path());
+    let out_dir = temp_dir.path().join("parts");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["-n", "2", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+    assert_eq!(
+        file_names(&out_dir),
+        ["reads.part_001.fq", "reads.part_002.fq"]
+    );
+    assert_eq!(
+        read_names(&out_dir.join("reads.part_001.fq")),
+        ["r1 BC=AAA", "r3 BC=AAA", "r5 BC=CCC"]
+    );
+
+    let out_dir = temp_dir.path().join("reads");
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["-r", "2", "-p", "chunk", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+    assert_eq!(
+        file_names(&out_dir),
+        [
+            "chunk.part_001.fq",
+            "chunk.part_002.fq",
+            "chunk.part_003.fq"
+        ]
+    );
+    assert_eq!(
+        read_names(&out_dir.join("chunk.part_003.fq")),
+        ["r5 BC=CCC"]
+    );
+}
+
+#[test]
+fn test_split_bases() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = write_fastq(temp_dir.path());
+    let out_dir = temp_dir.path().join("bases");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["-b", "12", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+
+    // 8+4, 6+2, 10
+    let parts: Vec<_> = file_names(&out_dir)
+        .iter()
+        .map(|name| read_names(&out_dir.join(name)).len())
+        .collect();
+    assert_eq!(parts, [2, 2, 1]);
+}
+
+#[test]
+fn test_split_by_name_compressed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = write_fastq(temp_dir.path());
+    let out_dir = temp_dir.path().join("barcodes");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["--by-name", r"BC=(\w+)", "-z", "gz", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+    assert_eq!(
+        file_names(&out_dir),
+        [
+            "reads.AAA.fq.gz",
+            "reads.CCC.fq.gz",
+            "reads.unmatched.fq.gz"
+        ]
+    );
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(out_dir.join("reads.AAA.fq.gz"));
+    let output = cmd.output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r1 BC=AAA\nACGTACGT\n>r3 BC=AAA\nACGTAC\n"
+    );
+}
+
+#[test]
+fn test_split_by_name_many_parts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.fastq");
+    // more barcodes than parts kept open, each seen twice far apart
+    let fastq: String = (0..2)
+        .flat_map(|round| (0..50).map(move |bc| format!("@r{round}_{bc} BC={bc}\nACGT\n+\nIIII\n")))
+        .collect();
+    fs::write(&input, fastq).unwrap();
+    let out_dir = temp_dir.path().join("barcodes");
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["--by-name", r"BC=(\w+)", "-z", "gz", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+    assert_eq!(file_names(&out_dir).len(), 50);
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(out_dir.join("reads.7.fq.gz"));
+    let output = cmd.output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        ">r0_7 BC=7\nACGT\n>r1_7 BC=7\nACGT\n"
+    );
+}
+
+#[test]
+fn test_split_many_parts_are_single_streams() {
+    // the empty BGZF block that ends every bgzip stream
+    const BGZF_EOF: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02,
+        0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input = temp_dir.path().join("reads.fastq");
+    let fastq: String = (0..400)
+        .map(|i| format!("@r{i}\nACGT\n+\nIIII\n"))
+        .collect();
+    fs::write(&input, fastq).unwrap();
+    let out_dir = temp_dir.path().join("parts");
+
+    // more parts than --by-name keeps open
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("split")
+        .arg(&input)
+        .args(["-n", "40", "-z", "gz", "-O"])
+        .arg(&out_dir);
+    cmd.assert().success();
+
+    let names = file_names(&out_dir);
+    assert_eq!(names.len(), 40);
+    for name in names {
+        let part = fs::read(out_dir.join(name)).unwrap();
+        let eofs = part
+            .windows(BGZF_EOF.len())
+            .filter(|window| *window == BGZF_EOF)
+            .count();
+        assert_eq!(eofs, 1);
+        assert!(part.ends_with(BGZF_EOF));
+    }
+
+    let mut cmd = Command::cargo_bin("rboss").unwrap();
+    cmd.arg("fq2fa").arg(out_dir.join("reads.part_040.fq.gz"));
+    let output = cmd.output().unwrap();
+    let names: Vec<_> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .step_by(2)
+        .map(String::from)
+        .collect();
+    assert_eq!(
+        names,
+        [">r39", ">r79", ">r119", ">r159", ">r199", ">r239", ">r279", ">r319", ">r359", ">r399"]
+    );
+}